@@ -0,0 +1,83 @@
+#[cfg(feature = "audio")]
+use ggez::audio::{SoundSource, Source};
+use ggez::Context;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sound {
+    Reveal,
+    Flag,
+    Detonate,
+    Win,
+}
+
+#[cfg(feature = "audio")]
+impl Sound {
+    const ALL: [Sound; 4] = [Sound::Reveal, Sound::Flag, Sound::Detonate, Sound::Win];
+
+    fn path(&self) -> &'static str {
+        match self {
+            Sound::Reveal => "/reveal.ogg",
+            Sound::Flag => "/flag.ogg",
+            Sound::Detonate => "/detonate.ogg",
+            Sound::Win => "/win.ogg",
+        }
+    }
+}
+
+/** Sound effects, indexed by `Sound`. Any that fail to load stay silent */
+#[cfg(feature = "audio")]
+pub struct Audio {
+    sources: Vec<Option<Source>>,
+}
+
+#[cfg(feature = "audio")]
+impl Audio {
+    pub fn load(ctx: &mut Context) -> Self {
+        let sources = Sound::ALL
+            .iter()
+            .map(|sound| match Source::new(ctx, sound.path()) {
+                Ok(source) => Some(source),
+                Err(err) => {
+                    eprintln!("could not load {}: {}", sound.path(), err);
+                    None
+                }
+            })
+            .collect();
+        Audio { sources }
+    }
+
+    /** Sound effects that never play, for tests and games loaded with
+    `GameState::from_json` */
+    #[cfg(any(test, feature = "serde"))]
+    pub fn silent() -> Self {
+        Audio {
+            sources: Sound::ALL.iter().map(|_| None).collect(),
+        }
+    }
+
+    pub fn play(&mut self, sound: Sound) {
+        if let Some(source) = &mut self.sources[sound as usize] {
+            if let Err(err) = source.play_detached() {
+                eprintln!("could not play {}: {}", sound.path(), err);
+            }
+        }
+    }
+}
+
+/** Stands in for the sound effects when built without the `audio` feature */
+#[cfg(not(feature = "audio"))]
+pub struct Audio;
+
+#[cfg(not(feature = "audio"))]
+impl Audio {
+    pub fn load(_ctx: &mut Context) -> Self {
+        Audio
+    }
+
+    #[cfg(any(test, feature = "serde"))]
+    pub fn silent() -> Self {
+        Audio
+    }
+
+    pub fn play(&mut self, _sound: Sound) {}
+}
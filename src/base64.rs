@@ -0,0 +1,91 @@
+//! Standard base64 with padding, enough to turn board bytes into shareable
+//! puzzle codes.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let sextets = [
+            b[0] >> 2,
+            (b[0] & 0b11) << 4 | b[1] >> 4,
+            (b[1] & 0b1111) << 2 | b[2] >> 6,
+            b[2] & 0b111111,
+        ];
+        for (i, sextet) in sextets.iter().enumerate() {
+            if i <= chunk.len() {
+                out.push(ALPHABET[*sextet as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/** Returns `None` for anything that isn't valid padded base64. Surrounding
+whitespace is ignored */
+pub fn decode(code: &str) -> Option<Vec<u8>> {
+    let code = code.trim().as_bytes();
+    if !code.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(code.len() / 4 * 3);
+    for (index, chunk) in code.chunks(4).enumerate() {
+        let last = index == code.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            return None;
+        }
+        let mut sextets = [0u8; 4];
+        for (i, &c) in chunk[..4 - padding].iter().enumerate() {
+            sextets[i] = ALPHABET.iter().position(|&a| a == c)? as u8;
+        }
+        let bytes = [
+            sextets[0] << 2 | sextets[1] >> 4,
+            sextets[1] << 4 | sextets[2] >> 2,
+            sextets[2] << 6 | sextets[3],
+        ];
+        out.extend_from_slice(&bytes[..3 - padding]);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+
+    #[test]
+    fn bytes_round_trip() {
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"mine"), "bWluZQ==");
+        for length in 0..10 {
+            let bytes = (0..length)
+                .map(|i| (i * 37 + 250) as u8)
+                .collect::<Vec<u8>>();
+            assert_eq!(decode(&encode(&bytes)), Some(bytes));
+        }
+    }
+
+    #[test]
+    fn puzzle_codes_survive_the_clipboard() {
+        let grid = Grid::with_mines(9, 9, &[(0, 0), (8, 8), (4, 2)]);
+        let pasted = format!("  {}\n", grid.to_code());
+        let decoded = Grid::from_code(&pasted).unwrap();
+        assert_eq!(decoded.mine_positions(), grid.mine_positions());
+    }
+
+    #[test]
+    fn invalid_codes_are_rejected() {
+        assert_eq!(decode("bWluZQ"), None);
+        assert_eq!(decode("bW=uZQ=="), None);
+        assert_eq!(decode("bWlu*Q=="), None);
+        assert_eq!(decode("b==="), None);
+    }
+}
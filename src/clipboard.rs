@@ -0,0 +1,26 @@
+use arboard::Error;
+
+/** The system clipboard, opened on first use and then kept open, since on
+X11 copied text is only there while the clipboard that copied it is */
+#[derive(Default)]
+pub struct Clipboard {
+    system: Option<arboard::Clipboard>,
+}
+
+impl Clipboard {
+    fn system(&mut self) -> Result<&mut arboard::Clipboard, Error> {
+        if self.system.is_none() {
+            self.system = Some(arboard::Clipboard::new()?);
+        }
+        Ok(self.system.as_mut().unwrap())
+    }
+
+    /** Puts `text` on the system clipboard */
+    pub fn copy(&mut self, text: &str) -> Result<(), Error> {
+        self.system()?.set_text(text)
+    }
+
+    pub fn paste(&mut self) -> Result<String, Error> {
+        self.system()?.get_text()
+    }
+}
@@ -1,92 +1,1450 @@
 use ggez::{
-    event::EventHandler,
-    graphics::{self, Color, DrawParam, Image},
+    event::{self, EventHandler, KeyCode, KeyMods},
+    filesystem,
+    graphics::{self, Color, DrawMode, DrawParam, Image, ImageFormat, Mesh, MeshBuilder, Rect},
     mint::{Point2, Vector2},
     timer::time_since_start,
     Context, GameResult,
 };
+#[cfg(feature = "serde")]
+use rand::SeedableRng;
+use rand::{rngs::StdRng, seq::SliceRandom};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use winit::MouseButton;
 
-use std::{fmt, path::Path, time::Duration};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    error::Error,
+    fmt, fs,
+    path::PathBuf,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use minesweeper::{
+    grid::{BoardState, Cell, CellState, CellView, Grid, Theme, Topology},
+    solver::{self, Action},
+};
+
+use crate::{
+    audio::{Audio, Sound},
+    clipboard::Clipboard,
+    keymap::{KeyAction, KeyMap},
+    scores::Scores,
+};
+
+pub const DEFAULT_UI_SCALE: f32 = 4.0;
+/** Zoom limits for `+` and `-` */
+const MIN_UI_SCALE: f32 = 1.0;
+const MAX_UI_SCALE: f32 = 8.0;
+/** The window is kept at least this many cells wide so the header fits,
+with narrower boards centered underneath */
+const MIN_COLUMNS: i32 = 32;
+/** Boards bigger than this many cells scroll inside the window */
+const MAX_VIEW_COLUMNS: i32 = 40;
+const MAX_VIEW_ROWS: i32 = 24;
+/** Frames between each cell of scrolling while the mouse is at the edge */
+const EDGE_SCROLL_FRAMES: u32 = 6;
+/** Largest pasted puzzle accepted, so the window stays on screen */
+const MAX_COLUMNS: i32 = 60;
+const MAX_ROWS: i32 = 30;
+/** Stands in for a minus sign in `number_to_sprites`. There's no sprite for
+it, so it's drawn as a line instead */
+const MINUS: u8 = u8::MAX - 1;
+/** Stands in for the colon between minutes and seconds, drawn as two dots */
+const COLON: u8 = u8::MAX;
+/** Stands in for the percent sign after the summary's efficiency, drawn as
+a slash between two dots */
+const PERCENT: u8 = u8::MAX - 2;
+/** How many cells of an opening are revealed each frame */
+const CASCADE_STEP: usize = 6;
+/** The header's timers stop at 99:59, the most `MM:SS` can show */
+const MAX_DISPLAY_SECONDS: u32 = 99 * 60 + 59;
+/** How many frames the board flashes red after a mistake in training */
+const WARNING_FRAMES: u32 = 20;
+/** How many mark changes `Ctrl+Z` can step back through */
+const UNDO_LIMIT: usize = 64;
+#[cfg(not(feature = "serde"))]
+const SAVE_PATH: &str = "save.txt";
+#[cfg(feature = "serde")]
+const SAVE_PATH: &str = "save.json";
+const SCORES_PATH: &str = "scores.json";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Difficulty {
+    Beginner,
+    Intermediate,
+    #[default]
+    Expert,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [
+        Difficulty::Beginner,
+        Difficulty::Intermediate,
+        Difficulty::Expert,
+    ];
+
+    /** The preset a board of this size and mine count was built from, if any */
+    pub fn matching(width: i32, height: i32, mine_count: i32) -> Option<Difficulty> {
+        Difficulty::ALL.iter().copied().find(|difficulty| {
+            difficulty.dimensions() == (width, height) && difficulty.mine_count() == mine_count
+        })
+    }
+
+    /** Board size as `(width, height)` */
+    pub fn dimensions(&self) -> (i32, i32) {
+        match self {
+            Difficulty::Beginner => (9, 9),
+            Difficulty::Intermediate => (16, 16),
+            Difficulty::Expert => (30, 16),
+        }
+    }
+
+    pub fn mine_count(&self) -> i32 {
+        match self {
+            Difficulty::Beginner => 10,
+            Difficulty::Intermediate => 40,
+            Difficulty::Expert => 99,
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Difficulty::Beginner => "beginner",
+            Difficulty::Intermediate => "intermediate",
+            Difficulty::Expert => "expert",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "beginner" => Ok(Difficulty::Beginner),
+            "intermediate" => Ok(Difficulty::Intermediate),
+            "expert" => Ok(Difficulty::Expert),
+            _ => Err(format!("unknown difficulty '{}'", s)),
+        }
+    }
+}
 
-use crate::grid::{BoardState, Grid};
+/** What it takes to win a game */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WinCondition {
+    /** Uncover every safe cell. Flags are optional */
+    #[default]
+    RevealAllSafe,
+    /** Flag every mine without flagging a safe cell. Uncovering every safe
+    cell still wins, since the leftover mines get flagged */
+    FlagAllMines,
+}
 
-pub const UI_SCALE: f32 = 4.0;
+/** Colors for the background and the numbers 1 through 8 */
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Palette {
+    #[default]
+    Classic,
+    /** Okabe-Ito colors, which stay distinct under the common kinds of
+    color blindness */
+    ColorBlind,
+}
 
-#[derive(PartialEq)]
+impl Palette {
+    pub fn toggled(&self) -> Palette {
+        match self {
+            Palette::Classic => Palette::ColorBlind,
+            Palette::ColorBlind => Palette::Classic,
+        }
+    }
+
+    pub fn background(&self) -> Color {
+        match self {
+            Palette::Classic => Color::new(60. / 255., 50. / 255., 83. / 255., 1.),
+            Palette::ColorBlind => Color::new(40. / 255., 40. / 255., 40. / 255., 1.),
+        }
+    }
+
+    /** Tint for an exposed cell with `count` neighboring mines */
+    pub fn number_color(&self, count: u8) -> Color {
+        let (r, g, b) = match (self, count) {
+            (Palette::Classic, 1) => (0.4, 0.6, 1.),
+            (Palette::Classic, 2) => (0.4, 0.9, 0.4),
+            (Palette::Classic, 3) => (1., 0.4, 0.4),
+            (Palette::Classic, 4) => (0.7, 0.5, 1.),
+            (Palette::Classic, 5) => (1., 0.6, 0.3),
+            (Palette::Classic, 6) => (0.3, 0.9, 0.9),
+            (Palette::Classic, 7) => (1., 1., 0.5),
+            (Palette::Classic, 8) => (0.7, 0.7, 0.7),
+            (Palette::ColorBlind, 1) => (0., 114. / 255., 178. / 255.),
+            (Palette::ColorBlind, 2) => (0., 158. / 255., 115. / 255.),
+            (Palette::ColorBlind, 3) => (213. / 255., 94. / 255., 0.),
+            (Palette::ColorBlind, 4) => (204. / 255., 121. / 255., 167. / 255.),
+            (Palette::ColorBlind, 5) => (230. / 255., 159. / 255., 0.),
+            (Palette::ColorBlind, 6) => (86. / 255., 180. / 255., 233. / 255.),
+            (Palette::ColorBlind, 7) => (240. / 255., 228. / 255., 66. / 255.),
+            (Palette::ColorBlind, 8) => (0.6, 0.6, 0.6),
+            _ => (1., 1., 1.),
+        };
+        Color::new(r, g, b, 1.)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PlayState {
     Unstarted,
+    /** Holds the time the game started */
     Playing(Duration),
+    Paused {
+        elapsed: Duration,
+    },
+    /** Holds how long the game took */
     Won(Duration),
     Lost(Duration),
 }
 
-pub struct GameState {
+impl PlayState {
+    /** The board only takes clicks before and during play. Paused and
+    finished games are locked */
+    pub fn accepts_input(&self) -> bool {
+        matches!(self, PlayState::Unstarted | PlayState::Playing(_))
+    }
+}
+
+/** Sizes in unscaled pixels that drawing and click mapping lay the game
+out by. Cells are as big as the sprites */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpriteConfig {
+    pub cell_px: f32,
+    /** Height of the counters above the board */
+    pub header_px: f32,
+}
+
+impl Default for SpriteConfig {
+    fn default() -> Self {
+        SpriteConfig {
+            cell_px: 8.,
+            header_px: 24.,
+        }
+    }
+}
+
+impl SpriteConfig {
+    /** Window size in screen pixels needed to show `grid` along with its
+    header at `ui_scale` */
+    pub fn window_size(&self, grid: &Grid, ui_scale: f32) -> (f32, f32) {
+        let (columns, rows) = view_size(grid);
+        (
+            (columns.max(MIN_COLUMNS) as f32 + row_shift(grid, 1)) * self.cell_px * ui_scale,
+            (rows as f32 * self.cell_px + self.header_px) * ui_scale,
+        )
+    }
+}
+
+/** Something the player did that can change the board */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Input {
+    Reveal(i32, i32),
+    Mark(i32, i32),
+    Chord(i32, i32),
+    ChordAll,
+    Undo,
+    Hint,
+    SolveStep,
+    /** Turning auto-flag on or off */
+    AutoFlag(bool),
+    #[cfg(feature = "debug")]
+    RevealMines,
+}
+
+/** An input, recorded so the game can be replayed */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InputEvent {
+    /** Time into the game the input happened */
+    pub at: Duration,
+    pub input: Input,
+}
+
+/** End of game statistics, shown over the board once it's won or lost */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GameSummary {
+    pub time: Duration,
+    pub turns: i32,
+    pub flags: i32,
+    pub wrong_flags: u32,
+    /** Mine clicks caught in training mode */
+    pub mistakes: i32,
+    pub hints: i32,
+    pub board_3bv: u32,
+    pub efficiency: f32,
+}
+
+/** The parts of `GameState` worth persisting. A game in progress stores its
+elapsed time rather than its start time, which only means something within
+the process that recorded it */
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct SavedGame<G> {
     total_mines: i32,
     total_flags: i32,
     turns: i32,
+    /** Saves from before this was kept load as unassisted */
+    #[serde(default)]
+    assisted: bool,
+    play_state: PlayState,
+    grid: G,
+}
+
+/** A board and the game being played on it. Races give each player one,
+side by side, all dealt the same mines */
+#[derive(Clone)]
+struct Board {
+    total_flags: i32,
+    turns: i32,
+    hints_used: i32,
+    /** Clicks on mines that training mode caught */
+    mistakes: i32,
+    /** Set once a hint had to reveal a cell the solver couldn't prove safe,
+    the mine chances were shown during play, the solver or auto-flag made a
+    move for the player, or training caught a mistake */
+    assisted: bool,
+    /** Set the first time a flag goes down, even if it's taken back later */
+    flags_used: bool,
     play_state: PlayState,
     grid: Grid,
-    spritesheet: Vec<Image>,
+    cursor: (i32, i32),
+    /** Top left cell in view, for boards too big to show whole */
+    camera: (i32, i32),
+    /** Cells already exposed on the grid that are still drawn covered, so
+    openings spread out over a few frames */
+    reveal_queue: VecDeque<(i32, i32)>,
+    /** Marks changed so far with the state each cell had before, newest
+    last. Uncovering can't be undone */
+    mark_history: VecDeque<(i32, i32, CellState)>,
+    /** Mine chances for the board as it was in `probabilities_board`, so
+    they're only worked out again after it changes */
+    probabilities: HashMap<(i32, i32), f32>,
+    probabilities_board: Vec<CellView>,
+    /** Set when `no_guess` couldn't find a board in time and settled for
+    one that may need a guess */
+    may_need_guess: bool,
+    /** Frames left of the red flash after a caught mistake */
+    warning_frames: u32,
+    /** The board and random state the current game started from, so its
+    inputs can be replayed */
+    start_grid: Grid,
+    start_rng: StdRng,
+    start_auto_flag: bool,
+    inputs: Vec<InputEvent>,
 }
 
-impl GameState {
-    pub fn new(total_mines: i32, grid: Grid, spritesheet: Vec<Image>) -> Self {
-        GameState {
-            total_mines,
+impl Board {
+    fn new(grid: Grid, rng: &StdRng, auto_flag: bool) -> Self {
+        Board {
             total_flags: 0,
             turns: 0,
+            hints_used: 0,
+            mistakes: 0,
+            assisted: false,
+            flags_used: false,
             play_state: PlayState::Unstarted,
+            start_grid: grid.clone(),
             grid,
-            spritesheet,
+            cursor: (0, 0),
+            camera: (0, 0),
+            reveal_queue: VecDeque::new(),
+            mark_history: VecDeque::new(),
+            probabilities: HashMap::new(),
+            probabilities_board: vec![],
+            may_need_guess: false,
+            warning_frames: 0,
+            start_rng: rng.clone(),
+            start_auto_flag: auto_flag,
+            inputs: vec![],
         }
     }
+
+    /** Time spent playing so far, not counting pauses */
+    fn elapsed(&self, now: Duration) -> Duration {
+        match self.play_state {
+            PlayState::Playing(start_time) => now.checked_sub(start_time).unwrap_or_default(),
+            PlayState::Paused { elapsed } => elapsed,
+            PlayState::Won(elapsed) | PlayState::Lost(elapsed) => elapsed,
+            PlayState::Unstarted => Duration::default(),
+        }
+    }
+
+    /** Whole seconds for the header's timer, which stops at 99:59. Scores
+    still use the full time */
+    fn display_seconds(&self, now: Duration) -> u32 {
+        self.elapsed(now).as_secs().min(MAX_DISPLAY_SECONDS as u64) as u32
+    }
+
+    /** The header's timer as `MM:SS` */
+    fn formatted_time(&self, now: Duration) -> String {
+        format_time(self.display_seconds(now) as u64)
+    }
+
+    /** Pausing freezes the timer, and resuming shifts the start time so
+    the timer picks up where it left off */
+    fn toggle_pause(&mut self, now: Duration) {
+        self.play_state = match self.play_state {
+            PlayState::Playing(start_time) => PlayState::Paused {
+                elapsed: now - start_time,
+            },
+            PlayState::Paused { elapsed } => PlayState::Playing(now - elapsed),
+            play_state => play_state,
+        };
+    }
+
+    /** Whether the game was won without ever placing a flag */
+    fn was_flagless(&self) -> bool {
+        matches!(self.play_state, PlayState::Won(_)) && !self.flags_used
+    }
+
+    /** How close the player's clicks came to the fewest possible, as a
+    fraction where 1.0 is perfect */
+    fn efficiency(&self) -> f32 {
+        if self.turns == 0 {
+            return 0.;
+        }
+        self.grid.board_3bv() as f32 / self.turns as f32
+    }
+
+    /** Statistics for the game so far. The time only counts once the game
+    is paused or over */
+    fn summary(&self) -> GameSummary {
+        let time = match self.play_state {
+            PlayState::Won(elapsed) | PlayState::Lost(elapsed) => elapsed,
+            PlayState::Paused { elapsed } => elapsed,
+            PlayState::Unstarted | PlayState::Playing(_) => Duration::default(),
+        };
+        GameSummary {
+            time,
+            turns: self.turns,
+            flags: self.total_flags,
+            wrong_flags: self.grid.wrong_flag_count(),
+            mistakes: self.mistakes,
+            hints: self.hints_used,
+            board_3bv: self.grid.board_3bv(),
+            efficiency: self.efficiency(),
+        }
+    }
+
+    /** Top left corner of a cell in unscaled pixels, given how far the
+    view is scrolled */
+    fn cell_origin(&self, sprites: SpriteConfig, x: i32, y: i32) -> (f32, f32) {
+        let cell = sprites.cell_px;
+        let (columns, _) = view_size(&self.grid);
+        let margin = (MIN_COLUMNS - columns).max(0) as f32 * cell / 2.;
+        (
+            margin + ((x - self.camera.0) as f32 + row_shift(&self.grid, y)) * cell,
+            sprites.header_px + (y - self.camera.1) as f32 * cell,
+        )
+    }
+
+    /** Whether a cell is inside the scrolled view */
+    fn in_view(&self, x: i32, y: i32) -> bool {
+        let (columns, rows) = view_size(&self.grid);
+        let (left, top) = self.camera;
+        x >= left && x < left + columns && y >= top && y < top + rows
+    }
+
+    /** Scrolls the view by whole cells, keeping it on the board */
+    fn pan(&mut self, dx: i32, dy: i32) {
+        let (columns, rows) = view_size(&self.grid);
+        self.camera = (
+            (self.camera.0 + dx).clamp(0, self.grid.width - columns),
+            (self.camera.1 + dy).clamp(0, self.grid.height - rows),
+        );
+    }
+
+    /** Scrolls just far enough to bring a cell into view */
+    fn scroll_to(&mut self, x: i32, y: i32) {
+        let (columns, rows) = view_size(&self.grid);
+        let (left, top) = self.camera;
+        let dx = (x - left).min(0) + (x - (left + columns - 1)).max(0);
+        let dy = (y - top).min(0) + (y - (top + rows - 1)).max(0);
+        self.pan(dx, dy);
+    }
+
+    /** A fresh board shaped like the current one */
+    fn empty_grid(&self) -> Grid {
+        let (width, height) = (self.grid.width, self.grid.height);
+        let mut grid = if self.grid.wraps() {
+            Grid::new_wrapping(width, height)
+        } else {
+            Grid::new(width, height)
+        };
+        grid.set_topology(self.grid.topology());
+        grid.set_neighbor_mode(self.grid.neighbor_mode());
+        grid
+    }
+
+    fn remember_mark(&mut self, x: i32, y: i32, before: CellState) {
+        if self.mark_history.len() == UNDO_LIMIT {
+            self.mark_history.pop_front();
+        }
+        self.mark_history.push_back((x, y, before));
+    }
 }
 
-impl EventHandler for GameState {
-    fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
+pub struct GameState {
+    total_mines: i32,
+    /** One board, or one per player in a race */
+    boards: Vec<Board>,
+    /** The board clicks and keys go to. Races pick it by which side of the
+    window the mouse is on */
+    active: usize,
+    /** Only draw the cursor while the keyboard is in use */
+    show_cursor: bool,
+    /** The cell under the mouse on the active board, if any */
+    hovered: Option<(i32, i32)>,
+    /** Where the mouse last was, relative to the active board */
+    mouse: Option<(f32, f32)>,
+    /** Frames until edge scrolling moves the view again */
+    scroll_delay: u32,
+    palette: Palette,
+    theme: Theme,
+    /** Flag the neighbors of any number that can only be mines after each
+    turn */
+    auto_flag: bool,
+    /** Tint covered cells by their chance of holding a mine */
+    show_probabilities: bool,
+    pub sprites: SpriteConfig,
+    /** Screen pixels per sprite pixel */
+    ui_scale: f32,
+    /** Set while the right button is held, so dragging flags each covered
+    cell the mouse passes over */
+    drag_flagging: bool,
+    /** The cell the drag was last over, so jitter within it does nothing */
+    last_flagged: Option<(i32, i32)>,
+    /** Start of the window title, followed by the game's status */
+    pub title: String,
+    /** The title last shown, so the window is only retitled on a change */
+    shown_title: String,
+    /** Lay mines so the board never needs a guess */
+    pub no_guess: bool,
+    /** Catch clicks that would hit a mine instead of ending the game */
+    pub training: bool,
+    pub win_condition: WinCondition,
+    /** How many steps out from a click openings spread, or `None` to clear
+    the whole opening */
+    pub reveal_depth: Option<u32>,
+    /** Ignore clicks and keys that would change the board, leaving only
+    looking around, zooming and display toggles */
+    pub read_only: bool,
+    pub keymap: KeyMap,
+    rng: StdRng,
+    scores: Scores,
+    clipboard: Clipboard,
+    spritesheet: Vec<Image>,
+    audio: Audio,
+    /** Sounds the game logic asked for, played on the next `update` */
+    sounds: Vec<Sound>,
+}
+
+impl GameState {
+    pub fn new(
+        total_mines: i32,
+        grid: Grid,
+        rng: StdRng,
+        spritesheet: Vec<Image>,
+        audio: Audio,
+    ) -> Self {
+        GameState {
+            total_mines,
+            boards: vec![Board::new(grid, &rng, false)],
+            active: 0,
+            show_cursor: false,
+            hovered: None,
+            mouse: None,
+            scroll_delay: 0,
+            palette: Palette::default(),
+            theme: Theme::default(),
+            auto_flag: false,
+            show_probabilities: false,
+            sprites: SpriteConfig::default(),
+            ui_scale: DEFAULT_UI_SCALE,
+            drag_flagging: false,
+            last_flagged: None,
+            title: "minesweeper".to_string(),
+            shown_title: String::new(),
+            no_guess: false,
+            training: false,
+            win_condition: WinCondition::default(),
+            reveal_depth: None,
+            read_only: false,
+            keymap: KeyMap::default(),
+            rng,
+            scores: Scores::load(SCORES_PATH),
+            clipboard: Clipboard::default(),
+            spritesheet,
+            audio,
+            sounds: vec![],
+        }
+    }
+
+    /** Splits the window between `players` boards for a race, dealing them
+    all a fresh one */
+    pub fn race(&mut self, players: usize) {
+        self.boards = vec![self.board().clone(); players.max(1)];
+        self.reset(self.total_mines, self.board().grid.clone());
+    }
+
+    /** Whether the window is split between players racing on the same mines */
+    fn racing(&self) -> bool {
+        self.boards.len() > 1
+    }
+
+    /** The board clicks and keys go to */
+    fn board(&self) -> &Board {
+        &self.boards[self.active]
+    }
+
+    fn board_mut(&mut self) -> &mut Board {
+        &mut self.boards[self.active]
+    }
+
+    /** Starts a new game on `grid`. A race deals every board the same mines
+    with the same opening already uncovered, so it doesn't hinge on each
+    player's first click */
+    pub fn reset(&mut self, total_mines: i32, grid: Grid) {
+        self.total_mines = total_mines;
+        let grid = if self.racing() {
+            race_board(grid, total_mines, &mut self.rng)
+        } else {
+            grid
+        };
+        let board = Board::new(grid, &self.rng, self.auto_flag);
+        for slot in self.boards.iter_mut() {
+            *slot = board.clone();
+        }
+    }
+
+    /** Restarts the active board's game and plays `events` back on it. Mines
+    come from the same random state, so the same inputs give the same board.
+    The events keep their spacing, with the last one landing at `now` */
+    pub fn replay(&mut self, now: Duration, events: Vec<InputEvent>) {
+        let auto_flag = self.auto_flag;
+        let origin = events
+            .last()
+            .and_then(|last| now.checked_sub(last.at))
+            .unwrap_or(now);
+        let start = self.board();
+        let board = Board::new(
+            start.start_grid.clone(),
+            &start.start_rng,
+            start.start_auto_flag,
+        );
+        self.rng = board.start_rng.clone();
+        self.auto_flag = board.start_auto_flag;
+        *self.board_mut() = board;
+        for event in events {
+            if !self.board().play_state.accepts_input() {
+                break;
+            }
+            self.apply_input(origin + event.at, event.input);
+        }
+        // Replays play out at once, so they'd only make a racket
+        self.sounds.clear();
+        self.auto_flag = auto_flag;
+    }
+
+    /** Acts on an input and records it */
+    fn apply_input(&mut self, now: Duration, input: Input) {
+        // A race runs on one clock, started by the first input on any board
+        if self.racing() {
+            for board in self.boards.iter_mut() {
+                if board.play_state == PlayState::Unstarted {
+                    board.play_state = PlayState::Playing(now);
+                }
+            }
+        }
+        let at = self.elapsed(now);
+        self.board_mut().inputs.push(InputEvent { at, input });
+        match input {
+            Input::Reveal(x, y) => self.reveal(now, x, y),
+            Input::Mark(x, y) => self.mark(x, y),
+            Input::Chord(x, y) => self.chord(now, x, y),
+            Input::ChordAll => self.chord_all(now),
+            Input::Undo => self.undo_mark(),
+            Input::Hint => self.hint(now),
+            Input::SolveStep => self.solve_step(now),
+            Input::AutoFlag(on) => {
+                self.auto_flag = on;
+                if on
+                    && matches!(
+                        self.board().play_state,
+                        PlayState::Playing(_) | PlayState::Paused { .. }
+                    )
+                {
+                    self.flag_forced_mines();
+                }
+            }
+            #[cfg(feature = "debug")]
+            Input::RevealMines => self.reveal_mines(),
+        }
+        self.claim_win(now);
+    }
+
+    /** Whether the board meets `win_condition` */
+    pub fn is_won(&self) -> bool {
+        match self.win_condition {
+            WinCondition::RevealAllSafe => self.board().grid.is_won(),
+            WinCondition::FlagAllMines => self.board().grid.all_mines_flagged(),
+        }
+    }
+
+    /** Ends a game in play as won once `is_won` holds, for wins that come
+    from flagging rather than uncovering the last safe cell */
+    fn claim_win(&mut self, now: Duration) {
+        if matches!(self.board().play_state, PlayState::Playing(_)) && self.is_won() {
+            self.update_play_state(now, BoardState::Cleared);
+        }
+    }
+
+    /** Mines left to find, assuming every flag is right. Goes negative once
+    there are more flags than mines */
+    pub fn mines_remaining(&self) -> i32 {
+        self.total_mines - self.board().total_flags
+    }
+
+    /** Time spent playing so far, not counting pauses */
+    fn elapsed(&self, now: Duration) -> Duration {
+        self.board().elapsed(now)
+    }
+
+    /** Top left corner of a cell in unscaled pixels, given how far the
+    view is scrolled */
+    fn cell_origin(&self, x: i32, y: i32) -> (f32, f32) {
+        self.board().cell_origin(self.sprites, x, y)
+    }
+
+    /** Stamps the active board's grid with the time into its game, for
+    the moves it records */
+    fn set_clock(&mut self, now: Duration) {
+        let elapsed = self.elapsed(now);
+        self.board_mut().grid.set_clock(elapsed);
+    }
+
+    /** Pauses or resumes every board, so no racer gets time off the clock */
+    fn toggle_pause(&mut self, now: Duration) {
+        for board in self.boards.iter_mut() {
+            board.toggle_pause(now);
+        }
+    }
+
+    /** Scrolls a cell at a time while the mouse rests within half a cell of
+    the view's edge */
+    fn edge_scroll(&mut self) {
+        let (x, y) = match self.mouse {
+            Some(position) => position,
+            None => return,
+        };
+        let cell = self.sprites.cell_px;
+        let (columns, rows) = view_size(&self.board().grid);
+        let camera = self.board().camera;
+        let (left, top) = self.cell_origin(camera.0, camera.1);
+        let view_x = (x / self.ui_scale - left) / cell;
+        let view_y = (y / self.ui_scale - top) / cell;
+        if view_x < 0. || view_y < 0. || view_x >= columns as f32 || view_y >= rows as f32 {
+            self.scroll_delay = 0;
+            return;
+        }
+        let direction = |position: f32, length: i32| {
+            if position < 0.5 {
+                -1
+            } else if position > length as f32 - 0.5 {
+                1
+            } else {
+                0
+            }
+        };
+        let (dx, dy) = (direction(view_x, columns), direction(view_y, rows));
+        if (dx, dy) == (0, 0) {
+            self.scroll_delay = 0;
+            return;
+        }
+        if self.scroll_delay > 0 {
+            self.scroll_delay -= 1;
+            return;
+        }
+        self.scroll_delay = EDGE_SCROLL_FRAMES;
+        self.board_mut().pan(dx, dy);
+        self.hovered = self.screen_to_grid(x, y);
+    }
+
+    /** Zooms by `step`, keeping the scale within limits and the window
+    fitted to the board */
+    fn zoom(&mut self, ctx: &mut Context, step: f32) {
+        self.ui_scale = (self.ui_scale + step).clamp(MIN_UI_SCALE, MAX_UI_SCALE);
+        if let Err(err) = self.fit_window(ctx) {
+            eprintln!("could not resize the window: {}", err);
+        }
+    }
+
+    /** Size in screen pixels of one board along with its header */
+    fn board_size(&self) -> (f32, f32) {
+        self.sprites.window_size(&self.board().grid, self.ui_scale)
+    }
+
+    /** Window size in screen pixels that fits every board side by side */
+    fn window_size(&self) -> (f32, f32) {
+        let (width, height) = self.board_size();
+        (width * self.boards.len() as f32, height)
+    }
+
+    /** Makes the board under window position `x` the active one, returning
+    `x` relative to that board. A right-drag stays on the board it started
+    on, so it can't flag cells on a rival's */
+    fn select_board(&mut self, x: f32) -> f32 {
+        let (width, _) = self.board_size();
+        if !self.drag_flagging {
+            self.active = ((x / width).max(0.) as usize).min(self.boards.len() - 1);
+        }
+        x - self.active as f32 * width
+    }
+
+    /** Resizes the window to fit the current board, e.g. after loading a
+    save of a different size */
+    fn fit_window(&self, ctx: &mut Context) -> GameResult {
+        let (width, height) = self.window_size();
+        if graphics::drawable_size(ctx) != (width, height) {
+            graphics::set_drawable_size(ctx, width, height)?;
+            graphics::set_screen_coordinates(ctx, Rect::new(0., 0., width, height))?;
+        }
         Ok(())
     }
 
-    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
-        let grid_x = (x / (8. * UI_SCALE)) as i32;
-        let grid_y = (y / (8. * UI_SCALE) - 3.) as i32;
-        match button {
-            MouseButton::Left => {
-                if y >= (24. * UI_SCALE) {
-                    if self.play_state == PlayState::Unstarted {
-                        self.play_state = PlayState::Playing(time_since_start(ctx));
-                        if let Some(cell) = self.grid.get(grid_x, grid_y) {
-                            if cell.has_mine {}
-                        }
-                    }
-                    match self.grid.uncover(grid_x, grid_y) {
-                        BoardState::InProgress => {}
-                        BoardState::Cleared => {
-                            self.play_state = PlayState::Won(time_since_start(ctx))
-                        }
-                        BoardState::Detonated => {
-                            self.play_state = PlayState::Lost(time_since_start(ctx))
-                        }
-                    };
-                    self.turns += 1;
-                }
-            }
-            MouseButton::Right => {
-                self.total_flags += self.grid.toggle_flag(grid_x, grid_y);
-            }
-            _ => {}
+    /** Maps a window position to the cell under it. Returns `None` for the
+    header and anywhere off the board */
+    pub fn screen_to_grid(&self, x: f32, y: f32) -> Option<(i32, i32)> {
+        let (columns, rows) = view_size(&self.board().grid);
+        let camera = self.board().camera;
+        let (_, top) = self.cell_origin(camera.0, camera.1);
+        let view_y = ((y / self.ui_scale - top) / self.sprites.cell_px).floor();
+        let (left, _) = self.cell_origin(camera.0, camera.1 + view_y as i32);
+        let view_x = ((x / self.ui_scale - left) / self.sprites.cell_px).floor();
+        if view_x < 0. || view_y < 0. || view_x >= columns as f32 || view_y >= rows as f32 {
+            return None;
+        }
+        Some((view_x as i32 + camera.0, view_y as i32 + camera.1))
+    }
+
+    /** Uncovers a covered cell, or chords an exposed one */
+    fn reveal(&mut self, now: Duration, x: i32, y: i32) {
+        let cell = match self.board().grid.get(x, y) {
+            Some(cell) => cell,
+            None => return,
+        };
+        if cell.is_exposed() {
+            self.chord(now, x, y);
+            return;
+        }
+        if cell.is_flagged() {
+            return;
+        }
+        let board = &mut self.boards[self.active];
+        if board.play_state == PlayState::Unstarted {
+            board.play_state = PlayState::Playing(now);
+        }
+        board.grid.set_clock(board.elapsed(now));
+        // Mines are seeded on the first click around its neighborhood, so it
+        // lands on an opening unless the board is too dense for one. Shared
+        // puzzles come with theirs already laid
+        if board.grid.mine_count() == 0 {
+            if self.no_guess {
+                // The solver trusts flags, so lay mines on a clean board and
+                // bring the marks across after
+                let mut grid = board.empty_grid();
+                board.may_need_guess =
+                    !grid.populate_no_guess(self.total_mines, (x, y), &mut self.rng);
+                for (x, y, cell) in board.grid.iter_cells() {
+                    if cell.is_flagged() || cell.is_questioned() {
+                        grid.restore_mark(x, y, *cell.state());
+                    }
+                }
+                board.grid = grid;
+            } else {
+                board
+                    .grid
+                    .populate_around(self.total_mines, (x, y), &mut self.rng);
+            }
+        }
+        if self.training && board.grid.get(x, y).is_some_and(|cell| cell.has_mine) {
+            self.catch_mistake();
+            return;
+        }
+        let (board_state, exposed) = board.grid.uncover_to_depth(x, y, self.reveal_depth);
+        board.reveal_queue.extend(exposed);
+        self.finish_turn(now, board_state);
+    }
+
+    /** Uncovers the neighbors of an exposed number once its flags are all
+    placed */
+    fn chord(&mut self, now: Duration, x: i32, y: i32) {
+        let cell = self.board().grid.get(x, y);
+        if !cell.is_some_and(|cell| cell.is_exposed()) {
+            return;
+        }
+        self.set_clock(now);
+        if self.training && self.board().grid.clone().chord(x, y) == BoardState::Detonated {
+            self.catch_mistake();
+            return;
+        }
+        let board_state = self.board_mut().grid.chord(x, y);
+        self.finish_turn(now, board_state);
+    }
+
+    /** Chords every number whose mines are all flagged, until nothing more
+    opens up */
+    fn chord_all(&mut self, now: Duration) {
+        if !matches!(self.board().play_state, PlayState::Playing(_)) {
+            return;
+        }
+        self.set_clock(now);
+        if self.training && self.board().grid.clone().auto_chord_all() == BoardState::Detonated {
+            self.catch_mistake();
+            return;
+        }
+        let board_state = self.board_mut().grid.auto_chord_all();
+        self.finish_turn(now, board_state);
+    }
+
+    /** Flashes the board instead of letting a training click hit a mine.
+    The game no longer counts towards best times */
+    fn catch_mistake(&mut self) {
+        let board = self.board_mut();
+        board.mistakes += 1;
+        board.warning_frames = WARNING_FRAMES;
+        board.assisted = true;
+    }
+
+    fn finish_turn(&mut self, now: Duration, board_state: BoardState) {
+        if board_state == BoardState::InProgress {
+            self.sounds.push(Sound::Reveal);
+            if self.auto_flag {
+                self.flag_forced_mines();
+            }
+        }
+        self.update_play_state(now, board_state);
+        self.board_mut().turns += 1;
+    }
+
+    /** Flags every cell the solver's flagging rule proves is a mine */
+    fn flag_forced_mines(&mut self) {
+        let board = self.board_mut();
+        for action in solver::deduce(&board.grid) {
+            if let Action::Flag(x, y) = action {
+                let flagged = board.grid.flag(x, y) > 0;
+                board.flags_used |= flagged;
+                board.assisted |= flagged;
+            }
+        }
+        board.total_flags = board.grid.count_flags();
+    }
+
+    /** Cycles the mark on a covered cell, keeping the flag count in step */
+    fn mark(&mut self, x: i32, y: i32) {
+        let board = &mut self.boards[self.active];
+        let before = board.grid.get(x, y).map(|cell| *cell.state());
+        let flags = board.grid.cycle_mark(x, y);
+        board.total_flags += flags;
+        board.flags_used |= flags > 0;
+        if let Some(before) = before {
+            if board.grid.get(x, y).map(|cell| *cell.state()) != Some(before) {
+                board.remember_mark(x, y, before);
+                self.sounds.push(Sound::Flag);
+            }
+        }
+    }
+
+    /** Reverts the most recent mark change that can still be reverted */
+    fn undo_mark(&mut self) {
+        let board = self.board_mut();
+        while let Some((x, y, before)) = board.mark_history.pop_back() {
+            let current = board.grid.get(x, y).map(|cell| *cell.state());
+            board.total_flags += board.grid.restore_mark(x, y, before);
+            if board.grid.get(x, y).map(|cell| *cell.state()) != current {
+                return;
+            }
+        }
+    }
+
+    /** Flags the cell a right-drag has just moved onto, once per cell */
+    fn drag_to(&mut self, now: Duration, x: i32, y: i32) {
+        if !self.drag_flagging || self.last_flagged == Some((x, y)) {
+            return;
+        }
+        self.last_flagged = Some((x, y));
+        let cell = self.board().grid.get(x, y);
+        let covered = cell.is_some_and(|cell| cell.is_covered());
+        // Marking a covered cell always flags it
+        if covered {
+            self.apply_input(now, Input::Mark(x, y));
+        }
+    }
+
+    /** Runs a rebindable action. Board actions apply at the keyboard cursor */
+    fn key_action(&mut self, now: Duration, action: KeyAction) {
+        let (x, y) = self.board().cursor;
+        let locked = !self.board().play_state.accepts_input();
+        let input = match action {
+            KeyAction::Pause => return self.toggle_pause(now),
+            _ if self.read_only => return,
+            KeyAction::Restart => return self.reset(self.total_mines, self.board().empty_grid()),
+            _ if locked => return,
+            KeyAction::Hint => return self.apply_input(now, Input::Hint),
+            KeyAction::Reveal => Input::Reveal(x, y),
+            KeyAction::Flag => Input::Mark(x, y),
+            KeyAction::Chord => Input::Chord(x, y),
+        };
+        self.show_cursor = true;
+        self.apply_input(now, input);
+    }
+
+    /** Whether the game hasn't been won or lost yet */
+    fn in_game(&self) -> bool {
+        !matches!(
+            self.board().play_state,
+            PlayState::Won(_) | PlayState::Lost(_)
+        )
+    }
+
+    /** Whether clicks and keys on the board are ignored, because the game
+    is over or paused or the board is read-only */
+    fn locked(&self) -> bool {
+        self.read_only || !self.board().play_state.accepts_input()
+    }
+
+    /** Handles a mouse button going down at (x, y) in screen pixels */
+    fn click(&mut self, now: Duration, button: MouseButton, x: f32, y: f32) {
+        let x = self.select_board(x);
+        if self.locked() {
+            return;
+        }
+        let (grid_x, grid_y) = match self.screen_to_grid(x, y) {
+            Some(coord) => coord,
+            None => return,
+        };
+        let input = match button {
+            MouseButton::Left => Input::Reveal(grid_x, grid_y),
+            MouseButton::Right => Input::Mark(grid_x, grid_y),
+            MouseButton::Middle => Input::Chord(grid_x, grid_y),
+            _ => return,
+        };
+        self.show_cursor = false;
+        self.apply_input(now, input);
+        if button == MouseButton::Right {
+            self.drag_flagging = true;
+            self.last_flagged = Some((grid_x, grid_y));
+        }
+    }
+
+    /** Handles the mouse moving to (x, y) in screen pixels, highlighting
+    the cell under it and carrying on any right-drag */
+    fn hover(&mut self, now: Duration, x: f32, y: f32) {
+        let x = self.select_board(x);
+        self.mouse = Some((x, y));
+        self.hovered = self.screen_to_grid(x, y);
+        if self.locked() {
+            return;
+        }
+        if let Some((grid_x, grid_y)) = self.hovered {
+            self.drag_to(now, grid_x, grid_y);
+        }
+    }
+
+    /** Moves the keyboard cursor, keeping it on the board */
+    fn move_cursor(&mut self, dx: i32, dy: i32) {
+        self.show_cursor = true;
+        let board = self.board_mut();
+        board.cursor = (
+            (board.cursor.0 + dx).clamp(0, board.grid.width - 1),
+            (board.cursor.1 + dy).clamp(0, board.grid.height - 1),
+        );
+        board.scroll_to(board.cursor.0, board.cursor.1);
+    }
+
+    fn update_play_state(&mut self, now: Duration, board_state: BoardState) {
+        let elapsed = self.elapsed(now);
+        match board_state {
+            BoardState::InProgress => {}
+            BoardState::Cleared => {
+                let board = self.board_mut();
+                board.play_state = PlayState::Won(elapsed);
+                board.grid.flag_all_mines();
+                board.total_flags = board.grid.count_flags();
+                self.sounds.push(Sound::Win);
+                self.record_score(elapsed);
+            }
+            BoardState::Detonated => {
+                self.board_mut().play_state = PlayState::Lost(elapsed);
+                self.sounds.push(Sound::Detonate);
+            }
+        }
+    }
+
+    /** Saves the winning time if it's a new best for a standard board.
+    Hints that had to guess and races don't count */
+    fn record_score(&mut self, elapsed: Duration) {
+        if self.racing() {
+            return;
+        }
+        let grid = &self.board().grid;
+        let difficulty = Difficulty::matching(grid.width, grid.height, self.total_mines);
+        if let Some(difficulty) = difficulty {
+            if !self.board().assisted && self.scores.record(difficulty, elapsed) {
+                if let Err(err) = self.scores.save(SCORES_PATH) {
+                    eprintln!("could not save scores to {}: {}", SCORES_PATH, err);
+                }
+            }
+        }
+    }
+
+    /** Applies every move the solver can currently deduce, as one turn */
+    fn solve_step(&mut self, now: Duration) {
+        if !matches!(self.board().play_state, PlayState::Playing(_)) {
+            return;
+        }
+        let actions = solver::deduce(&self.board().grid);
+        if actions.is_empty() {
+            return;
+        }
+        self.set_clock(now);
+        let board = &mut self.boards[self.active];
+        board.assisted = true;
+        let mut reveals = vec![];
+        for action in actions {
+            match action {
+                Action::Flag(x, y) => board.flags_used |= board.grid.flag(x, y) > 0,
+                Action::Reveal(x, y) => reveals.push((x, y)),
+            }
+        }
+        board.total_flags = board.grid.count_flags();
+        // Wrong flags can lead the solver onto a mine
+        if self.training && board.grid.clone().uncover_many(&reveals) == BoardState::Detonated {
+            self.catch_mistake();
+            return;
+        }
+        let board_state = board.grid.uncover_many(&reveals);
+        self.finish_turn(now, board_state);
+    }
+
+    /** Uncovers one cell the solver can prove is safe, falling back to a
+    random safe cell when nothing can be deduced */
+    fn hint(&mut self, now: Duration) {
+        if !matches!(self.board().play_state, PlayState::Playing(_)) {
+            return;
+        }
+        let grid = &self.boards[self.active].grid;
+        // Wrong flags can lead the solver astray, so double check each reveal
+        let is_safe = |x: i32, y: i32| grid.get(x, y).is_some_and(|cell| !cell.has_mine);
+        let deduced = solver::deduce(grid)
+            .into_iter()
+            .find_map(|action| match action {
+                Action::Reveal(x, y) if is_safe(x, y) => Some((x, y)),
+                _ => None,
+            });
+        let target = match deduced {
+            Some(target) => Some(target),
+            None => {
+                let candidates = grid
+                    .snapshot()
+                    .iter()
+                    .filter(|cell| {
+                        (cell.state == CellState::Covered || cell.state == CellState::Questioned)
+                            && is_safe(cell.x, cell.y)
+                    })
+                    .map(|cell| (cell.x, cell.y))
+                    .collect::<Vec<(i32, i32)>>();
+                let target = candidates.choose(&mut self.rng).copied();
+                self.board_mut().assisted |= target.is_some();
+                target
+            }
+        };
+
+        if let Some((x, y)) = target {
+            self.board_mut().hints_used += 1;
+            self.set_clock(now);
+            let board_state = self.board_mut().grid.uncover(x, y);
+            self.update_play_state(now, board_state);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self, now: Duration) -> serde_json::Result<String> {
+        let board = self.board();
+        let play_state = match board.play_state {
+            PlayState::Playing(_) => PlayState::Playing(board.elapsed(now)),
+            play_state => play_state,
+        };
+        serde_json::to_string(&SavedGame {
+            total_mines: self.total_mines,
+            total_flags: board.total_flags,
+            turns: board.turns,
+            assisted: board.assisted,
+            play_state,
+            grid: &board.grid,
+        })
+    }
+
+    /** A saved game with no sounds. There's no clock to resume against
+    yet, so a game saved in progress comes back paused */
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str, spritesheet: Vec<Image>) -> serde_json::Result<GameState> {
+        let mut state = GameState::new(
+            0,
+            Grid::new(1, 1),
+            StdRng::from_entropy(),
+            spritesheet,
+            Audio::silent(),
+        );
+        state.apply_json(json)?;
+        Ok(state)
+    }
+
+    /** Swaps in a saved game on every board, starting over the undo history
+    and the record of inputs from it. A game saved in progress is left
+    paused */
+    #[cfg(feature = "serde")]
+    fn apply_json(&mut self, json: &str) -> serde_json::Result<()> {
+        let saved: SavedGame<Grid> = serde_json::from_str(json)?;
+        if let Err(mismatched) = saved.grid.validate() {
+            let (x, y) = mismatched[0];
+            return Err(serde::de::Error::custom(format!(
+                "mine count at ({}, {}) doesn't match its neighbors",
+                x, y
+            )));
+        }
+        self.reset(saved.total_mines, saved.grid);
+        for board in self.boards.iter_mut() {
+            board.total_flags = saved.total_flags;
+            board.turns = saved.turns;
+            board.assisted = saved.assisted;
+            board.play_state = match saved.play_state {
+                PlayState::Playing(elapsed) => PlayState::Paused { elapsed },
+                play_state => play_state,
+            };
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "serde"))]
+    fn save(&self, _now: Duration) -> Result<(), Box<dyn Error>> {
+        Ok(fs::write(SAVE_PATH, self.board().grid.serialize())?)
+    }
+
+    #[cfg(feature = "serde")]
+    fn save(&self, now: Duration) -> Result<(), Box<dyn Error>> {
+        Ok(fs::write(SAVE_PATH, self.to_json(now)?)?)
+    }
+
+    #[cfg(not(feature = "serde"))]
+    fn restore(&mut self, now: Duration) -> Result<(), Box<dyn Error>> {
+        self.apply_text(now, &fs::read_to_string(SAVE_PATH)?)
+    }
+
+    /** Swaps in a saved grid on every board, resuming play if its mines
+    were already laid. The save doesn't keep the time taken, so a resumed game
+    can't set a best time */
+    #[cfg(not(feature = "serde"))]
+    fn apply_text(&mut self, now: Duration, text: &str) -> Result<(), Box<dyn Error>> {
+        let grid = Grid::deserialize(text)?;
+        let flags = grid.count_flags();
+        let play_state = if grid.is_won() {
+            self.reset(grid.mine_count(), grid);
+            PlayState::Won(Duration::default())
+        } else if grid.mine_count() > 0 {
+            self.reset(grid.mine_count(), grid);
+            PlayState::Playing(now)
+        } else {
+            self.reset(self.total_mines, grid);
+            PlayState::Unstarted
+        };
+        for board in self.boards.iter_mut() {
+            board.play_state = play_state;
+            board.assisted = matches!(play_state, PlayState::Playing(_));
+            board.total_flags = flags;
+        }
+        Ok(())
+    }
+
+    /** Loads the saved game and picks up its timer where it left off. A
+    timer that ran longer than this window has been open stays paused */
+    #[cfg(feature = "serde")]
+    fn restore(&mut self, now: Duration) -> Result<(), Box<dyn Error>> {
+        self.apply_json(&fs::read_to_string(SAVE_PATH)?)?;
+        for board in self.boards.iter_mut() {
+            if let PlayState::Paused { elapsed } = board.play_state {
+                if let Some(start_time) = now.checked_sub(elapsed) {
+                    board.play_state = PlayState::Playing(start_time);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /** Shows where every mine is without ending the game, for checking how
+    mines get laid. Only in `debug` builds */
+    #[cfg(feature = "debug")]
+    fn reveal_mines(&mut self) {
+        self.board_mut().grid.uncover_bombs();
+    }
+
+    fn copy_code(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.board().grid.mine_count() == 0 {
+            return Err("mines aren't laid until the first click".into());
+        }
+        Ok(self.clipboard.copy(&self.board().grid.to_code())?)
+    }
+
+    /** Starts the puzzle whose code is on the clipboard */
+    fn paste_code(&mut self) -> Result<(), Box<dyn Error>> {
+        let grid = Grid::from_code(&self.clipboard.paste()?)?;
+        if grid.width > MAX_COLUMNS || grid.height > MAX_ROWS {
+            return Err(format!(
+                "a {}x{} board won't fit on screen, the limit is {}x{}",
+                grid.width, grid.height, MAX_COLUMNS, MAX_ROWS
+            )
+            .into());
+        }
+        self.reset(grid.mine_count(), grid);
+        Ok(())
+    }
+
+    /** Moves time-based effects on by a frame */
+    fn advance(&mut self) {
+        for sound in self.sounds.drain(..) {
+            self.audio.play(sound);
+        }
+        self.edge_scroll();
+        for board in self.boards.iter_mut() {
+            let step = CASCADE_STEP.min(board.reveal_queue.len());
+            board.reveal_queue.drain(..step);
+            board.warning_frames = board.warning_frames.saturating_sub(1);
+
+            if self.show_probabilities {
+                // Leaning on the odds doesn't earn a best time
+                board.assisted |= matches!(board.play_state, PlayState::Playing(_));
+                let snapshot = board.grid.snapshot();
+                if snapshot != board.probabilities_board {
+                    board.probabilities = solver::probabilities(&board.grid);
+                    board.probabilities_board = snapshot;
+                }
+            }
+        }
+    }
+
+    /** The game's state in a few words, for window titles */
+    fn status(&self, now: Duration) -> String {
+        if self.racing() {
+            let statuses = self
+                .boards
+                .iter()
+                .enumerate()
+                .map(|(player, board)| {
+                    let mines_remaining = self.total_mines - board.total_flags;
+                    let status = status_text(board.play_state, board.elapsed(now), mines_remaining);
+                    format!("{}: {}", player + 1, status)
+                })
+                .collect::<Vec<String>>();
+            return statuses.join(" | ");
+        }
+        let status = status_text(
+            self.board().play_state,
+            self.elapsed(now),
+            self.mines_remaining(),
+        );
+        if self.board().may_need_guess && self.in_game() {
+            return format!("{} \u{2014} may need a guess", status);
+        }
+        status
+    }
+
+    /** Draws `sprite` in the header's `cursor_x`th cell, drawing `MINUS` and
+    `COLON` by hand since the spritesheet has no room for them */
+    fn draw_header_sprite(
+        &self,
+        ctx: &mut Context,
+        sprite: u8,
+        cursor_x: i32,
+        color: Color,
+    ) -> GameResult<()> {
+        let size = self.sprites.cell_px;
+        draw_sprite(
+            ctx,
+            &self.spritesheet,
+            size,
+            sprite,
+            (cursor_x as f32 * size, size),
+            color,
+        )
+    }
+
+    /** The end of game summary's rows as an icon sprite, its tint and the
+    text beside it. The time reads like the header's timer */
+    fn summary_rows(&self, board: &Board) -> Vec<(usize, Color, String)> {
+        let summary = board.summary();
+        let time = summary.time.as_secs().min(MAX_DISPLAY_SECONDS as u64);
+        let mut rows = vec![
+            (15, graphics::WHITE, format_time(time)),
+            (14, graphics::WHITE, summary.turns.to_string()),
+            (11, graphics::WHITE, summary.flags.to_string()),
+            (13, graphics::WHITE, summary.board_3bv.to_string()),
+            (
+                14,
+                Color::new(0.4, 0.9, 0.4, 1.),
+                format!("{}%", (summary.efficiency * 100.).round()),
+            ),
+        ];
+        // A win leaves no room for wrong flags, so only a loss lists them
+        if let PlayState::Lost(_) = board.play_state {
+            rows.insert(
+                3,
+                (
+                    11,
+                    Color::new(1., 0.2, 0.2, 1.),
+                    summary.wrong_flags.to_string(),
+                ),
+            );
+        }
+        if self.training {
+            rows.push((
+                self.theme.mine(),
+                Color::new(1., 0.2, 0.2, 1.),
+                summary.mistakes.to_string(),
+            ));
         }
+        if summary.hints > 0 {
+            rows.push((12, Color::new(1., 0.8, 0.2, 1.), summary.hints.to_string()));
+        }
+        rows
     }
 
-    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        graphics::clear(ctx, Color::new(60. / 255., 50. / 255., 83. / 255., 1.));
+    /** Draws the header and board of `self.boards[index]` with their left
+    edge `left` screen pixels into the window, without clearing it first */
+    fn draw_board(&self, ctx: &mut Context, index: usize, left: f32) -> GameResult<()> {
+        let board = &self.boards[index];
+        let (size, header) = (self.sprites.cell_px, self.sprites.header_px);
+        let (columns, rows) = view_size(&board.grid);
+        let full_width = columns.max(MIN_COLUMNS) as f32 * size;
+        let board_height = rows as f32 * size;
 
         // Set UI scale
         let transform = DrawParam::new()
+            .dest(Point2 { x: left, y: 0. })
             .scale(Vector2 {
-                x: UI_SCALE,
-                y: UI_SCALE,
+                x: self.ui_scale,
+                y: self.ui_scale,
             })
             .to_matrix();
         graphics::set_transform(ctx, transform);
@@ -95,85 +1453,1471 @@ impl EventHandler for GameState {
         // Draw UI
         let mut cursor_x = 1;
         {
-            // Draw Turn Counter
+            // Draw Timer
             let sprite_params = DrawParam::new().dest(Point2 {
-                x: cursor_x as f32 * 8.,
-                y: 1 as f32 * 8.,
+                x: cursor_x as f32 * size,
+                y: size,
             });
             graphics::draw(ctx, &self.spritesheet[15], sprite_params)?;
             cursor_x += 1;
-            let seconds_since_start = match self.play_state {
-                PlayState::Won(end_time) => end_time.as_secs(),
-                PlayState::Lost(end_time) => end_time.as_secs(),
-                PlayState::Playing(start_time) => (time_since_start(ctx) - start_time).as_secs(),
-                PlayState::Unstarted => 0,
+            for sprite in text_to_sprites(&board.formatted_time(time_since_start(ctx))) {
+                self.draw_header_sprite(ctx, sprite, cursor_x, graphics::WHITE)?;
+                cursor_x += 1;
+            }
+
+            // Draw the best time for this board, in gold
+            let difficulty =
+                Difficulty::matching(board.grid.width, board.grid.height, self.total_mines);
+            if let Some(best) = difficulty.and_then(|difficulty| self.scores.best(difficulty)) {
+                let gold = Color::new(1., 0.8, 0.2, 1.);
+                cursor_x += 1;
+                let sprite_params = DrawParam::new()
+                    .dest(Point2 {
+                        x: cursor_x as f32 * size,
+                        y: size,
+                    })
+                    .color(gold);
+                graphics::draw(ctx, &self.spritesheet[15], sprite_params)?;
+                cursor_x += 1;
+                let best = best.as_secs().min(MAX_DISPLAY_SECONDS as u64);
+                for sprite in text_to_sprites(&format_time(best)) {
+                    self.draw_header_sprite(ctx, sprite, cursor_x, gold)?;
+                    cursor_x += 1;
+                }
+            }
+
+            cursor_x += 1;
+            // Draw Turn Counter
+            let sprite_params = DrawParam::new().dest(Point2 {
+                x: cursor_x as f32 * size,
+                y: size,
+            });
+            graphics::draw(ctx, &self.spritesheet[14], sprite_params)?;
+            cursor_x += 1;
+            // Once cleared, show how efficient those turns were as a percentage
+            let turns = match board.play_state {
+                PlayState::Won(_) => (board.efficiency() * 100.).round() as i32,
+                _ => board.turns,
             };
-            for sprite in number_to_sprites(seconds_since_start as i32) {
-                let sprite_params = DrawParam::new().dest(Point2 {
-                    x: cursor_x as f32 * 8.,
-                    y: 1 as f32 * 8.,
-                });
-                graphics::draw(ctx, &self.spritesheet[sprite as usize], sprite_params)?;
+            for sprite in number_to_sprites(turns) {
+                self.draw_header_sprite(ctx, sprite, cursor_x, graphics::WHITE)?;
                 cursor_x += 1;
             }
 
             cursor_x += 1;
             // Draw Flag Counter
             let sprite_params = DrawParam::new().dest(Point2 {
-                x: cursor_x as f32 * 8.,
-                y: 1 as f32 * 8.,
+                x: cursor_x as f32 * size,
+                y: size,
             });
             graphics::draw(ctx, &self.spritesheet[11], sprite_params)?;
             cursor_x += 1;
-            for sprite in number_to_sprites(self.total_flags) {
-                let sprite_params = DrawParam::new().dest(Point2 {
-                    x: cursor_x as f32 * 8.,
-                    y: 1 as f32 * 8.,
-                });
-                graphics::draw(ctx, &self.spritesheet[sprite as usize], sprite_params)?;
+            for sprite in number_to_sprites(board.total_flags) {
+                self.draw_header_sprite(ctx, sprite, cursor_x, graphics::WHITE)?;
                 cursor_x += 1;
             }
 
             cursor_x += 1;
-            // Draw Mine Counter
+            // Draw Remaining Mines, which goes negative when over-flagged
             let sprite_params = DrawParam::new().dest(Point2 {
-                x: cursor_x as f32 * 8.,
-                y: 1 as f32 * 8.,
+                x: cursor_x as f32 * size,
+                y: size,
             });
-            graphics::draw(ctx, &self.spritesheet[10], sprite_params)?;
+            graphics::draw(ctx, &self.spritesheet[self.theme.mine()], sprite_params)?;
             cursor_x += 1;
-            for sprite in number_to_sprites(self.total_mines) {
-                let sprite_params = DrawParam::new().dest(Point2 {
-                    x: cursor_x as f32 * 8.,
-                    y: 1 as f32 * 8.,
-                });
-                graphics::draw(ctx, &self.spritesheet[sprite as usize], sprite_params)?;
+            for sprite in number_to_sprites(self.total_mines - board.total_flags) {
+                self.draw_header_sprite(ctx, sprite, cursor_x, graphics::WHITE)?;
                 cursor_x += 1;
             }
         }
 
+        // Draw progress bar along the bottom of the header
+        let safe_cells = board.grid.width * board.grid.height - self.total_mines;
+        let uncovered = safe_cells - board.grid.remaining_safe_cells().clamp(0, safe_cells);
+        if uncovered > 0 {
+            let progress = Mesh::new_rectangle(
+                ctx,
+                DrawMode::fill(),
+                Rect::new(
+                    0.,
+                    header - size * 3. / 8.,
+                    full_width * uncovered as f32 / safe_cells as f32,
+                    1.,
+                ),
+                Color::new(0.4, 0.9, 0.4, 1.),
+            )?;
+            graphics::draw(ctx, &progress, DrawParam::new())?;
+        }
+
         // Draw minefield
-        for y in 0..self.grid.height {
-            for x in 0..self.grid.width {
-                let sprite_params = DrawParam::new().dest(Point2 {
-                    x: x as f32 * 8.,
-                    y: (y as f32 * 8. + 24.),
-                });
-                graphics::draw(
+        let paused = matches!(board.play_state, PlayState::Paused { .. });
+        let playing = matches!(board.play_state, PlayState::Playing(_));
+        let cascading = board.reveal_queue.iter().collect::<HashSet<&(i32, i32)>>();
+        for (x, y, cell) in board.grid.iter_cells() {
+            if !board.in_view(x, y) {
+                continue;
+            }
+            let (left, top) = board.cell_origin(self.sprites, x, y);
+            // Hide the board while paused so it can't be studied off the clock
+            if paused || cascading.contains(&(x, y)) {
+                let sprite_params = DrawParam::new().dest(Point2 { x: left, y: top });
+                graphics::draw(ctx, &self.spritesheet[self.theme.covered()], sprite_params)?;
+                continue;
+            }
+            let mut color = cell_color(cell, self.palette);
+            // Dim numbers whose mines are all flagged, since they need no
+            // more attention
+            if playing && board.grid.is_number_satisfied(x, y) {
+                color.a = 0.5;
+            }
+            draw_cell(
+                ctx,
+                &self.spritesheet,
+                size,
+                cell,
+                self.theme,
+                color,
+                (left, top),
+            )?;
+        }
+
+        // Tint covered cells from green to red by their chance of a mine
+        if self.show_probabilities && matches!(board.play_state, PlayState::Playing(_)) {
+            for (&(x, y), &chance) in board.probabilities.iter() {
+                if cascading.contains(&(x, y)) || !board.in_view(x, y) {
+                    continue;
+                }
+                let (left, top) = board.cell_origin(self.sprites, x, y);
+                let tint = Mesh::new_rectangle(
                     ctx,
-                    &self.spritesheet[self.grid.get(x, y).unwrap().sprite_index()],
-                    sprite_params,
+                    DrawMode::fill(),
+                    Rect::new(left, top, size, size),
+                    Color::new(chance, 1. - chance, 0., 0.4),
                 )?;
+                graphics::draw(ctx, &tint, DrawParam::new())?;
+            }
+        }
+
+        // Flash the board red, fading out, after a caught mistake
+        if board.warning_frames > 0 {
+            let fade = board.warning_frames as f32 / WARNING_FRAMES as f32;
+            let warning = Mesh::new_rectangle(
+                ctx,
+                DrawMode::fill(),
+                Rect::new(0., header, full_width, board_height),
+                Color::new(1., 0.2, 0.2, 0.4 * fade),
+            )?;
+            graphics::draw(ctx, &warning, DrawParam::new())?;
+        }
+
+        // Draw a minimap of the whole board in the header when it scrolls,
+        // with the part in view outlined
+        if columns < board.grid.width || rows < board.grid.height {
+            let scale = ((header - size) / board.grid.height as f32).min(1.);
+            let map_width = board.grid.width as f32 * scale;
+            let (map_left, map_top) = (full_width - 3. * size - map_width, size / 2.);
+            let mut map = MeshBuilder::new();
+            map.rectangle(
+                DrawMode::fill(),
+                Rect::new(
+                    map_left,
+                    map_top,
+                    map_width,
+                    board.grid.height as f32 * scale,
+                ),
+                Color::new(0.5, 0.5, 0.5, 1.),
+            );
+            for (x, y, cell) in board.grid.iter_cells() {
+                let color = match cell.state() {
+                    CellState::Exposed => Color::new(0.9, 0.9, 0.9, 1.),
+                    CellState::Flagged => Color::new(1., 0.2, 0.2, 1.),
+                    _ => continue,
+                };
+                map.rectangle(
+                    DrawMode::fill(),
+                    Rect::new(
+                        map_left + x as f32 * scale,
+                        map_top + y as f32 * scale,
+                        scale,
+                        scale,
+                    ),
+                    color,
+                );
+            }
+            map.rectangle(
+                DrawMode::stroke(0.5),
+                Rect::new(
+                    map_left + board.camera.0 as f32 * scale,
+                    map_top + board.camera.1 as f32 * scale,
+                    columns as f32 * scale,
+                    rows as f32 * scale,
+                ),
+                Color::new(1., 0.8, 0.2, 1.),
+            );
+            let map = map.build(ctx)?;
+            graphics::draw(ctx, &map, DrawParam::new())?;
+        }
+
+        // Draw a gold crossed-out flag in the corner for a flagless win
+        if board.was_flagless() {
+            let left = full_width - 2. * size;
+            let gold = Color::new(1., 0.8, 0.2, 1.);
+            let sprite_params = DrawParam::new()
+                .dest(Point2 { x: left, y: size })
+                .color(gold);
+            graphics::draw(ctx, &self.spritesheet[11], sprite_params)?;
+            let strike = MeshBuilder::new()
+                .line(&[[left, size * 2.], [left + size, size]], 1., gold)?
+                .build(ctx)?;
+            graphics::draw(ctx, &strike, DrawParam::new())?;
+        }
+
+        // Draw hover highlight, only on the board the mouse is over
+        let active = index == self.active;
+        if let Some((x, y)) = self
+            .hovered
+            .filter(|&(x, y)| active && !paused && board.in_view(x, y))
+        {
+            let (left, top) = board.cell_origin(self.sprites, x, y);
+            let highlight = Mesh::new_rectangle(
+                ctx,
+                DrawMode::fill(),
+                Rect::new(left, top, size, size),
+                Color::new(1., 1., 1., 0.15),
+            )?;
+            graphics::draw(ctx, &highlight, DrawParam::new())?;
+        }
+
+        // Draw keyboard cursor
+        let (x, y) = board.cursor;
+        if self.show_cursor && active && board.in_view(x, y) {
+            let (left, top) = board.cell_origin(self.sprites, x, y);
+            let outline = Mesh::new_rectangle(
+                ctx,
+                DrawMode::stroke(1.),
+                Rect::new(left + 0.5, top + 0.5, size - 1., size - 1.),
+                Color::new(1., 0.8, 0.2, 1.),
+            )?;
+            graphics::draw(ctx, &outline, DrawParam::new())?;
+        }
+
+        // Draw the summary over a dimmed board once the game is over
+        if let PlayState::Won(_) | PlayState::Lost(_) = board.play_state {
+            let dim = Mesh::new_rectangle(
+                ctx,
+                DrawMode::fill(),
+                Rect::new(0., header, full_width, board_height),
+                Color::new(0., 0., 0., 0.6),
+            )?;
+            graphics::draw(ctx, &dim, DrawParam::new())?;
+
+            let rows = self.summary_rows(board);
+            let left = (full_width / 2. - 3. * size).floor();
+            let top = header
+                + ((board_height - rows.len() as f32 * size * 1.25) / 2.)
+                    .max(0.)
+                    .floor();
+            for (row, (icon, color, text)) in rows.iter().enumerate() {
+                let row_top = top + row as f32 * size * 1.25;
+                let sprite_params = DrawParam::new()
+                    .dest(Point2 {
+                        x: left,
+                        y: row_top,
+                    })
+                    .color(*color);
+                graphics::draw(ctx, &self.spritesheet[*icon], sprite_params)?;
+                for (i, sprite) in text_to_sprites(text).into_iter().enumerate() {
+                    let position = (left + (i + 2) as f32 * size, row_top);
+                    draw_sprite(
+                        ctx,
+                        &self.spritesheet,
+                        size,
+                        sprite,
+                        position,
+                        graphics::WHITE,
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl EventHandler for GameState {
+    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
+        self.advance();
+
+        // The clock only shows whole seconds, so this retitles the window
+        // at most once a second during play
+        let title = format!(
+            "{} \u{2014} {}",
+            self.title,
+            self.status(time_since_start(ctx))
+        );
+        if title != self.shown_title {
+            graphics::set_window_title(ctx, &title);
+            self.shown_title = title;
+        }
+        Ok(())
+    }
+
+    /** Keeps one screen pixel per window pixel so sprites aren't stretched */
+    fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
+        let _ = graphics::set_screen_coordinates(ctx, Rect::new(0., 0., width, height));
+    }
+
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        self.click(time_since_start(ctx), button, x, y);
+    }
+
+    fn mouse_button_up_event(&mut self, _ctx: &mut Context, button: MouseButton, _x: f32, _y: f32) {
+        if button == MouseButton::Right {
+            self.drag_flagging = false;
+            self.last_flagged = None;
+        }
+    }
+
+    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32, _dx: f32, _dy: f32) {
+        self.hover(time_since_start(ctx), x, y);
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        keycode: KeyCode,
+        keymods: KeyMods,
+        _repeat: bool,
+    ) {
+        let now = time_since_start(ctx);
+        if !keymods.contains(KeyMods::CTRL) {
+            if let Some(action) = self.keymap.action(keycode) {
+                self.key_action(now, action);
+                return;
+            }
+        }
+        let locked = self.locked();
+        // WASD moves the cursor, so saving and loading moved from S and L to
+        // Ctrl+S and Ctrl+L, and the solver step from D to X
+        match keycode {
+            KeyCode::S if keymods.contains(KeyMods::CTRL) => {
+                if let Err(err) = self.save(now) {
+                    eprintln!("could not save to {}: {}", SAVE_PATH, err);
+                }
+            }
+            KeyCode::L if keymods.contains(KeyMods::CTRL) && !self.read_only => {
+                if let Err(err) = self.restore(now) {
+                    eprintln!("could not load {}: {}", SAVE_PATH, err);
+                }
+                if let Err(err) = self.fit_window(ctx) {
+                    eprintln!("could not resize the window: {}", err);
+                }
+            }
+            KeyCode::C if keymods.contains(KeyMods::CTRL) => {
+                if let Err(err) = self.copy_code() {
+                    eprintln!("could not copy the puzzle code: {}", err);
+                }
+            }
+            KeyCode::V if keymods.contains(KeyMods::CTRL) && !self.read_only => {
+                if let Err(err) = self.paste_code() {
+                    eprintln!("could not load a puzzle code: {}", err);
+                }
+                if let Err(err) = self.fit_window(ctx) {
+                    eprintln!("could not resize the window: {}", err);
+                }
             }
+            KeyCode::E if keymods.contains(KeyMods::CTRL) && !locked => {
+                self.apply_input(now, Input::ChordAll)
+            }
+            KeyCode::Z if keymods.contains(KeyMods::CTRL) && !locked => {
+                self.apply_input(now, Input::Undo)
+            }
+            KeyCode::Up | KeyCode::W => self.move_cursor(0, -1),
+            KeyCode::Down | KeyCode::S => self.move_cursor(0, 1),
+            KeyCode::Left | KeyCode::A => self.move_cursor(-1, 0),
+            KeyCode::Right | KeyCode::D => self.move_cursor(1, 0),
+            KeyCode::R if keymods.contains(KeyMods::CTRL) && !self.read_only => {
+                let inputs = self.board().inputs.clone();
+                self.replay(now, inputs);
+            }
+            KeyCode::X if !locked => self.apply_input(now, Input::SolveStep),
+            KeyCode::C => self.palette = self.palette.toggled(),
+            KeyCode::T => self.theme = self.theme.cycled(),
+            KeyCode::O => self.show_probabilities = !self.show_probabilities,
+            // Once the game is over there's nothing left for auto-flag to
+            // change, so it doesn't need recording
+            KeyCode::G if self.read_only || !self.in_game() => self.auto_flag = !self.auto_flag,
+            KeyCode::G => self.apply_input(now, Input::AutoFlag(!self.auto_flag)),
+            KeyCode::F12 => match save_screenshot(ctx) {
+                Ok(path) => eprintln!("saved a screenshot to {}", path.display()),
+                Err(err) => eprintln!("could not save a screenshot: {}", err),
+            },
+            KeyCode::Equals | KeyCode::Add => self.zoom(ctx, 1.),
+            KeyCode::Minus | KeyCode::Subtract => self.zoom(ctx, -1.),
+            #[cfg(feature = "debug")]
+            KeyCode::M if !self.read_only => self.apply_input(now, Input::RevealMines),
+            KeyCode::Escape => event::quit(ctx),
+            _ => {}
         }
+    }
 
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        graphics::clear(ctx, self.palette.background());
+        let (board_width, _) = self.board_size();
+        for index in 0..self.boards.len() {
+            self.draw_board(ctx, index, index as f32 * board_width)?;
+        }
         graphics::present(ctx)
     }
 }
 
+/** Draws `sprite` with its top left corner at `(left, top)`, drawing
+`MINUS`, `COLON` and `PERCENT` by hand since the spritesheet has no room
+for them */
+fn draw_sprite(
+    ctx: &mut Context,
+    spritesheet: &[Image],
+    size: f32,
+    sprite: u8,
+    (left, top): (f32, f32),
+    color: Color,
+) -> GameResult<()> {
+    match sprite {
+        MINUS => {
+            let minus = MeshBuilder::new()
+                .line(
+                    &[
+                        [left + size / 8., top + size / 2.],
+                        [left + size * 7. / 8., top + size / 2.],
+                    ],
+                    1.,
+                    color,
+                )?
+                .build(ctx)?;
+            graphics::draw(ctx, &minus, DrawParam::new())
+        }
+        COLON => {
+            let colon = MeshBuilder::new()
+                .rectangle(
+                    DrawMode::fill(),
+                    Rect::new(left + size * 3. / 8., top + size / 4., size / 4., size / 8.),
+                    color,
+                )
+                .rectangle(
+                    DrawMode::fill(),
+                    Rect::new(
+                        left + size * 3. / 8.,
+                        top + size * 5. / 8.,
+                        size / 4.,
+                        size / 8.,
+                    ),
+                    color,
+                )
+                .build(ctx)?;
+            graphics::draw(ctx, &colon, DrawParam::new())
+        }
+        PERCENT => {
+            let dot = size / 4.;
+            let percent = MeshBuilder::new()
+                .rectangle(
+                    DrawMode::fill(),
+                    Rect::new(left + size / 8., top + size / 8., dot, dot),
+                    color,
+                )
+                .line(
+                    &[
+                        [left + size * 3. / 4., top + size / 8.],
+                        [left + size / 4., top + size * 7. / 8.],
+                    ],
+                    1.,
+                    color,
+                )?
+                .rectangle(
+                    DrawMode::fill(),
+                    Rect::new(left + size * 5. / 8., top + size * 5. / 8., dot, dot),
+                    color,
+                )
+                .build(ctx)?;
+            graphics::draw(ctx, &percent, DrawParam::new())
+        }
+        _ => {
+            let sprite_params = DrawParam::new()
+                .dest(Point2 { x: left, y: top })
+                .color(color);
+            graphics::draw(ctx, &spritesheet[sprite as usize], sprite_params)
+        }
+    }
+}
+
+/** Draws `cell` as `theme` shows it, tinted `color`, crossing out wrong
+flags */
+fn draw_cell(
+    ctx: &mut Context,
+    spritesheet: &[Image],
+    size: f32,
+    cell: &Cell,
+    theme: Theme,
+    color: Color,
+    (left, top): (f32, f32),
+) -> GameResult<()> {
+    let sprite_params = DrawParam::new()
+        .dest(Point2 { x: left, y: top })
+        .color(color);
+    graphics::draw(ctx, &spritesheet[cell.sprite_index(theme)], sprite_params)?;
+    if *cell.state() == CellState::WronglyFlagged {
+        let cross = MeshBuilder::new()
+            .line(
+                &[[left, top], [left + size, top + size]],
+                1.,
+                Color::new(1., 0.2, 0.2, 1.),
+            )?
+            .line(
+                &[[left + size, top], [left, top + size]],
+                1.,
+                Color::new(1., 0.2, 0.2, 1.),
+            )?
+            .build(ctx)?;
+        graphics::draw(ctx, &cross, DrawParam::new())?;
+    }
+    Ok(())
+}
+
+/** Summary of the game for the window title, like "00:42 \u{2014} 10 mines
+left" */
+fn status_text(play_state: PlayState, elapsed: Duration, mines_remaining: i32) -> String {
+    match play_state {
+        PlayState::Won(_) => "You Win!".to_string(),
+        PlayState::Lost(_) => "Game Over".to_string(),
+        PlayState::Paused { .. } => "Paused".to_string(),
+        PlayState::Unstarted | PlayState::Playing(_) => format!(
+            "{} \u{2014} {} mines left",
+            format_time(elapsed.as_secs()),
+            mines_remaining
+        ),
+    }
+}
+
+/** How far row `y` is pushed right, in cells. Hex boards offset odd rows by
+half a cell so each cell touches the six it neighbors */
+fn row_shift(grid: &Grid, y: i32) -> f32 {
+    match grid.topology() {
+        Topology::Hex if y.rem_euclid(2) == 1 => 0.5,
+        _ => 0.,
+    }
+}
+
+/** Lays mines on `grid` around its middle and uncovers the opening there,
+unless it already has mines. Every racer is dealt a copy */
+fn race_board(mut grid: Grid, mine_count: i32, rng: &mut StdRng) -> Grid {
+    if grid.mine_count() > 0 {
+        return grid;
+    }
+    let (x, y) = (grid.width / 2, grid.height / 2);
+    grid.populate_around(mine_count, (x, y), rng);
+    grid.uncover(x, y);
+    grid
+}
+
+/** Columns and rows of `grid` that fit in the window at once */
+fn view_size(grid: &Grid) -> (i32, i32) {
+    (
+        grid.width.min(MAX_VIEW_COLUMNS),
+        grid.height.min(MAX_VIEW_ROWS),
+    )
+}
+
+/** Writes what's in the window to a PNG in the game's data folder,
+returning where it went */
+fn save_screenshot(ctx: &mut Context) -> GameResult<PathBuf> {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let path = screenshot_path(stamp);
+    graphics::screenshot(ctx)?.encode(ctx, ImageFormat::Png, &path)?;
+    Ok(filesystem::user_data_dir(ctx).join(path.trim_start_matches('/')))
+}
+
+/** Name for a screenshot taken `stamp` seconds after the Unix epoch,
+rooted in the game's data folder */
+fn screenshot_path(stamp: u64) -> String {
+    format!("/screenshot-{}.png", stamp)
+}
+
+/** `seconds` as `MM:SS`. Minutes keep counting past 99 */
+fn format_time(seconds: u64) -> String {
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+/** Sprite indices for a string of digits, `-`, `:` and `%`. Anything else is
+skipped */
+fn text_to_sprites(text: &str) -> Vec<u8> {
+    text.chars()
+        .filter_map(|c| match c {
+            '-' => Some(MINUS),
+            ':' => Some(COLON),
+            '%' => Some(PERCENT),
+            _ => c.to_digit(10).map(|digit| digit as u8),
+        })
+        .collect()
+}
+
+/** Sprite indices for the digits of `x`, most significant first and led by
+`MINUS` when negative */
 fn number_to_sprites(x: i32) -> Vec<u8> {
-    x.to_string()
-        .chars()
-        .map(|digit| digit.to_string().parse::<u8>().unwrap())
-        .collect::<Vec<u8>>()
+    let mut remaining = x.unsigned_abs();
+    let mut sprites = vec![];
+    loop {
+        sprites.push((remaining % 10) as u8);
+        remaining /= 10;
+        if remaining == 0 {
+            break;
+        }
+    }
+    if x < 0 {
+        sprites.push(MINUS);
+    }
+    sprites.reverse();
+    sprites
+}
+
+/** Tint for an exposed number from `palette`, or red for the mine that was
+hit. Everything else is drawn untinted */
+fn cell_color(cell: &Cell, palette: Palette) -> Color {
+    if *cell.state() == CellState::Detonated {
+        return Color::new(1., 0.3, 0.3, 1.);
+    }
+    if !cell.is_exposed() || cell.has_mine {
+        return graphics::WHITE;
+    }
+    palette.number_color(cell.neighboring_mines())
+}
+
+#[cfg(test)]
+mod tests {
+    use minesweeper::grid::NeighborMode;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    /** A game on `grid` that has no window, sounds or sprites */
+    fn game(total_mines: i32, grid: Grid) -> GameState {
+        GameState::new(
+            total_mines,
+            grid,
+            StdRng::seed_from_u64(7),
+            vec![],
+            Audio::silent(),
+        )
+    }
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    /** Applies `inputs` a second apart, the way the window would until
+    the game ends */
+    fn play(state: &mut GameState, inputs: &[Input]) {
+        for (second, &input) in inputs.iter().enumerate() {
+            if state.board().play_state.accepts_input() {
+                state.apply_input(secs(second as u64 + 1), input);
+            }
+        }
+    }
+
+    #[test]
+    fn replays_end_on_the_same_board() {
+        let mut state = game(30, Grid::new(16, 12));
+        play(
+            &mut state,
+            &[
+                Input::Mark(15, 11),
+                Input::Reveal(4, 4),
+                Input::AutoFlag(true),
+                Input::SolveStep,
+                Input::Hint,
+                Input::Mark(0, 0),
+                Input::Mark(1, 0),
+                Input::Undo,
+                Input::ChordAll,
+                Input::Hint,
+            ],
+        );
+        let board = state.board().grid.serialize();
+        let (flags, turns, hints) = (
+            state.board().total_flags,
+            state.board().turns,
+            state.board().hints_used,
+        );
+
+        let inputs = state.board().inputs.clone();
+        state.auto_flag = false;
+        state.replay(secs(10), inputs.clone());
+        assert_eq!(state.board().grid.serialize(), board);
+        assert_eq!(
+            (
+                state.board().total_flags,
+                state.board().turns,
+                state.board().hints_used
+            ),
+            (flags, turns, hints)
+        );
+        assert_eq!(state.board().inputs, inputs);
+        assert!(!state.auto_flag);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_saves_round_trip() {
+        let mut state = game(30, Grid::new(16, 12));
+        play(
+            &mut state,
+            &[Input::Reveal(4, 4), Input::Mark(0, 0), Input::Mark(15, 11)],
+        );
+        let json = state.to_json(secs(20)).unwrap();
+
+        let mut loaded = game(10, Grid::new(9, 9));
+        play(&mut loaded, &[Input::Mark(1, 1), Input::Reveal(3, 3)]);
+        loaded.apply_json(&json).unwrap();
+        assert_eq!(
+            loaded.board().grid.serialize(),
+            state.board().grid.serialize()
+        );
+        assert_eq!(
+            (
+                loaded.total_mines,
+                loaded.board().total_flags,
+                loaded.board().turns
+            ),
+            (
+                state.total_mines,
+                state.board().total_flags,
+                state.board().turns
+            )
+        );
+        assert_eq!(loaded.elapsed(secs(100)), state.elapsed(secs(20)));
+        assert!(loaded.board().inputs.is_empty());
+        assert!(loaded.board().mark_history.is_empty());
+        assert!(loaded.board().reveal_queue.is_empty());
+        assert_eq!(
+            loaded.board().start_grid.serialize(),
+            state.board().grid.serialize()
+        );
+
+        assert!(loaded.apply_json("{}").is_err());
+
+        let loaded = GameState::from_json(&json, vec![]).unwrap();
+        assert_eq!(
+            loaded.board().grid.serialize(),
+            state.board().grid.serialize()
+        );
+        assert!(loaded.board().play_state == PlayState::Paused { elapsed: secs(19) });
+        assert!(GameState::from_json("[]", vec![]).is_err());
+    }
+
+    #[test]
+    fn first_clicks_are_safe_on_dense_boards() {
+        // Room for the mines outside the neighborhood, so it opens up
+        let mut state = game(15, Grid::new(5, 5));
+        play(&mut state, &[Input::Reveal(2, 2)]);
+        assert_eq!(state.board().grid.mine_count(), 15);
+        assert_eq!(state.board().grid.get(2, 2).unwrap().neighboring_mines(), 0);
+        assert!(matches!(state.board().play_state, PlayState::Playing(_)));
+
+        // Too dense for an opening, but the click itself is still safe
+        let mut state = game(20, Grid::new(5, 5));
+        play(&mut state, &[Input::Reveal(2, 2)]);
+        assert_eq!(state.board().grid.mine_count(), 20);
+        assert!(state.board().grid.get(2, 2).unwrap().is_exposed());
+
+        let mut state = game(8, Grid::new(3, 3));
+        play(&mut state, &[Input::Reveal(0, 0)]);
+        assert!(matches!(state.board().play_state, PlayState::Won(_)));
+    }
+
+    #[test]
+    fn negative_numbers_lead_with_a_minus() {
+        assert_eq!(number_to_sprites(0), vec![0]);
+        assert_eq!(number_to_sprites(305), vec![3, 0, 5]);
+        assert_eq!(number_to_sprites(-7), vec![MINUS, 7]);
+        assert_eq!(number_to_sprites(-120), vec![MINUS, 1, 2, 0]);
+        assert_eq!(
+            number_to_sprites(i32::MIN),
+            vec![MINUS, 2, 1, 4, 7, 4, 8, 3, 6, 4, 8]
+        );
+    }
+
+    #[test]
+    fn restarts_keep_the_board_shape() {
+        let mut grid = Grid::new_wrapping(9, 9);
+        grid.set_topology(Topology::Hex);
+        grid.set_neighbor_mode(NeighborMode::Four);
+        let fresh = game(10, grid).board().empty_grid();
+        assert!(fresh.wraps());
+        assert_eq!(fresh.topology(), Topology::Hex);
+        assert_eq!(fresh.neighbor_mode(), NeighborMode::Four);
+    }
+
+    #[test]
+    fn read_only_boards_ignore_input() {
+        let mut state = game(1, Grid::with_mines(9, 9, &[(0, 0)]));
+        state.read_only = true;
+        let before = state.board().grid.snapshot();
+        let (left, top) = state.cell_origin(4, 4);
+        let (x, y) = ((left + 1.) * state.ui_scale, (top + 1.) * state.ui_scale);
+        assert_eq!(state.screen_to_grid(x, y), Some((4, 4)));
+        for &button in [MouseButton::Left, MouseButton::Right, MouseButton::Middle].iter() {
+            state.click(secs(1), button, x, y);
+        }
+        for &action in [KeyAction::Reveal, KeyAction::Flag, KeyAction::Chord].iter() {
+            state.key_action(secs(2), action);
+        }
+        state.key_action(secs(3), KeyAction::Restart);
+        assert!(state.board().grid.snapshot() == before);
+        assert_eq!(state.board().grid.mine_positions(), vec![(0, 0)]);
+        assert!(state.board().inputs.is_empty());
+        assert!(state.board().play_state == PlayState::Unstarted);
+    }
+
+    #[test]
+    fn hints_only_reveal_safe_cells() {
+        let mines = [(0, 0), (4, 4), (0, 4)];
+        let mut state = game(3, Grid::with_mines(5, 5, &mines));
+        play(
+            &mut state,
+            &[Input::Reveal(1, 1), Input::Mark(0, 0), Input::Hint],
+        );
+        let exposed = state
+            .board()
+            .grid
+            .iter_cells()
+            .filter(|(_, _, cell)| cell.is_exposed())
+            .collect::<Vec<_>>();
+        assert!(exposed.len() > 1);
+        assert!(exposed.iter().all(|(_, _, cell)| !cell.has_mine));
+        assert_eq!(state.board().summary().hints, 1);
+        // The flag pins the mine, so the hint was deduced rather than guessed
+        assert!(!state.board().assisted);
+    }
+
+    #[test]
+    fn efficiency_compares_turns_to_min_clicks() {
+        let mut state = game(1, Grid::with_mines(3, 1, &[(1, 0)]));
+        assert_eq!(state.board().efficiency(), 0.);
+        // The chord opens nothing, so it's a wasted turn
+        play(
+            &mut state,
+            &[Input::Reveal(0, 0), Input::Chord(0, 0), Input::Reveal(2, 0)],
+        );
+        assert!(matches!(state.board().play_state, PlayState::Won(_)));
+        assert_eq!(state.board().turns, 3);
+        assert_eq!(state.board().efficiency(), 2. / 3.);
+    }
+
+    #[test]
+    fn boundary_pixels_map_to_cells() {
+        // A 9 wide board is centered under the 32 cell header, 92 pixels in
+        // and 24 down, then drawn four times over
+        let state = game(10, Grid::new(9, 9));
+        assert_eq!(state.screen_to_grid(368., 96.), Some((0, 0)));
+        assert_eq!(state.screen_to_grid(399.9, 127.9), Some((0, 0)));
+        assert_eq!(state.screen_to_grid(400., 128.), Some((1, 1)));
+        assert_eq!(state.screen_to_grid(655.9, 383.9), Some((8, 8)));
+        // The header, the margins and past the last row
+        assert_eq!(state.screen_to_grid(400., 95.9), None);
+        assert_eq!(state.screen_to_grid(367.9, 200.), None);
+        assert_eq!(state.screen_to_grid(656., 200.), None);
+        assert_eq!(state.screen_to_grid(400., 384.), None);
+    }
+
+    #[test]
+    fn numbers_are_tinted_by_count() {
+        let expected = [
+            (0.4, 0.6, 1.),
+            (0.4, 0.9, 0.4),
+            (1., 0.4, 0.4),
+            (0.7, 0.5, 1.),
+            (1., 0.6, 0.3),
+            (0.3, 0.9, 0.9),
+            (1., 1., 0.5),
+            (0.7, 0.7, 0.7),
+        ];
+        let around = [
+            (0, 0),
+            (1, 0),
+            (2, 0),
+            (0, 1),
+            (2, 1),
+            (0, 2),
+            (1, 2),
+            (2, 2),
+        ];
+        for (count, &(r, g, b)) in (1..=8).zip(expected.iter()) {
+            let mut grid = Grid::with_mines(3, 3, &around[..count]);
+            grid.uncover(1, 1);
+            let cell = grid.get(1, 1).unwrap();
+            assert_eq!(cell.neighboring_mines() as usize, count);
+            assert_eq!(cell_color(&cell, Palette::Classic), Color::new(r, g, b, 1.));
+        }
+
+        // Blank cells, covered cells and mines keep their own colors
+        let mut grid = Grid::with_mines(3, 1, &[(0, 0)]);
+        grid.uncover(2, 0);
+        let color = |x| cell_color(&grid.get(x, 0).unwrap(), Palette::Classic);
+        assert_eq!(color(2), graphics::WHITE);
+        assert_eq!(color(0), graphics::WHITE);
+        grid.uncover(0, 0);
+        let hit = cell_color(&grid.get(0, 0).unwrap(), Palette::Classic);
+        assert_eq!(hit, Color::new(1., 0.3, 0.3, 1.));
+    }
+
+    #[test]
+    fn pausing_freezes_the_timer() {
+        let mut state = game(10, Grid::new(9, 9));
+        state.apply_input(secs(10), Input::Reveal(4, 4));
+        state.toggle_pause(secs(15));
+        assert_eq!(state.elapsed(secs(15)), secs(5));
+        assert_eq!(state.elapsed(secs(100)), secs(5));
+        assert!(!state.board().play_state.accepts_input());
+
+        // Resuming picks up where the timer stopped
+        state.toggle_pause(secs(100));
+        assert_eq!(state.elapsed(secs(100)), secs(5));
+        assert_eq!(state.elapsed(secs(103)), secs(8));
+        assert!(state.board().play_state.accepts_input());
+    }
+
+    #[test]
+    fn flagged_cells_are_never_uncovered() {
+        let mut state = game(1, Grid::with_mines(3, 1, &[(0, 0)]));
+        play(
+            &mut state,
+            &[Input::Reveal(1, 0), Input::Mark(0, 0), Input::Reveal(0, 0)],
+        );
+        assert!(state.board().grid.get(0, 0).unwrap().is_flagged());
+        assert!(matches!(state.board().play_state, PlayState::Playing(_)));
+        let mut grid = Grid::with_mines(3, 1, &[(0, 0)]);
+        grid.flag(0, 0);
+        assert_eq!(grid.uncover(0, 0), BoardState::InProgress);
+    }
+
+    #[test]
+    fn remaining_mines_can_go_negative() {
+        let mut state = game(1, Grid::with_mines(4, 1, &[(0, 0)]));
+        assert_eq!(state.mines_remaining(), 1);
+        assert_eq!(number_to_sprites(state.mines_remaining()), vec![1]);
+        play(&mut state, &[Input::Reveal(1, 0), Input::Mark(0, 0)]);
+        assert_eq!(state.mines_remaining(), 0);
+        assert_eq!(number_to_sprites(0), vec![0]);
+        state.apply_input(secs(3), Input::Mark(2, 0));
+        assert_eq!(state.mines_remaining(), -1);
+        assert_eq!(number_to_sprites(state.mines_remaining()), vec![MINUS, 1]);
+        assert_eq!(number_to_sprites(120), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn moves_queue_their_sounds() {
+        let mut state = game(1, Grid::with_mines(4, 1, &[(0, 0)]));
+        play(&mut state, &[Input::Reveal(1, 0), Input::Mark(0, 0)]);
+        assert_eq!(state.sounds, vec![Sound::Reveal, Sound::Flag]);
+        state.advance();
+        assert!(state.sounds.is_empty());
+        state.apply_input(secs(3), Input::Reveal(3, 0));
+        assert_eq!(state.sounds, vec![Sound::Win]);
+
+        let mut state = game(1, Grid::with_mines(4, 1, &[(0, 0)]));
+        play(&mut state, &[Input::Reveal(1, 0), Input::Reveal(0, 0)]);
+        assert_eq!(state.sounds, vec![Sound::Reveal, Sound::Detonate]);
+    }
+
+    #[test]
+    fn palettes_give_every_number_its_own_color() {
+        for &palette in [Palette::Classic, Palette::ColorBlind].iter() {
+            let colors = (1..=8)
+                .map(|count| palette.number_color(count))
+                .collect::<Vec<Color>>();
+            for (i, color) in colors.iter().enumerate() {
+                assert!(!colors[i + 1..].contains(color));
+                assert!(*color != palette.background());
+            }
+        }
+        assert!(Palette::Classic.toggled() == Palette::ColorBlind);
+        assert!(Palette::Classic.toggled().toggled() == Palette::Classic);
+        assert!(Palette::Classic.background() != Palette::ColorBlind.background());
+    }
+
+    /** Screen pixel inside cell (x, y) */
+    fn pixel(state: &GameState, x: i32, y: i32) -> (f32, f32) {
+        let (left, top) = state.cell_origin(x, y);
+        ((left + 1.) * state.ui_scale, (top + 1.) * state.ui_scale)
+    }
+
+    #[test]
+    fn right_drags_flag_each_cell_once() {
+        let mut state = game(2, Grid::with_mines(5, 5, &[(0, 0), (4, 4)]));
+        let (x, y) = pixel(&state, 1, 1);
+        state.click(secs(1), MouseButton::Right, x, y);
+        // Jitter within the first cell doesn't cycle it on to a question mark
+        state.drag_to(secs(1), 1, 1);
+        state.drag_to(secs(1), 1, 1);
+        state.drag_to(secs(2), 2, 1);
+        state.drag_to(secs(2), 2, 1);
+        state.drag_to(secs(3), 1, 1);
+        assert!(state.board().grid.get(1, 1).unwrap().is_flagged());
+        assert!(state.board().grid.get(2, 1).unwrap().is_flagged());
+        assert_eq!(state.board().total_flags, 2);
+    }
+
+    #[test]
+    fn undo_restores_the_previous_mark() {
+        let mut state = game(1, Grid::with_mines(4, 1, &[(0, 0)]));
+        play(
+            &mut state,
+            &[
+                Input::Reveal(1, 0),
+                Input::Mark(0, 0),
+                Input::Mark(0, 0),
+                Input::Undo,
+            ],
+        );
+        assert!(state.board().grid.get(0, 0).unwrap().is_flagged());
+        assert_eq!(state.board().total_flags, 1);
+        state.apply_input(secs(5), Input::Undo);
+        assert_eq!(
+            *state.board().grid.get(0, 0).unwrap().state(),
+            CellState::Covered
+        );
+        assert_eq!(state.board().total_flags, 0);
+        // Uncovering can't be undone
+        state.apply_input(secs(6), Input::Undo);
+        assert!(state.board().grid.get(1, 0).unwrap().is_exposed());
+    }
+
+    #[test]
+    fn finished_games_ignore_clicks() {
+        assert!(!BoardState::InProgress.is_terminal());
+        assert!(BoardState::Cleared.is_terminal());
+        assert!(BoardState::Detonated.is_terminal());
+
+        let mut state = game(1, Grid::with_mines(4, 1, &[(0, 0)]));
+        play(&mut state, &[Input::Reveal(1, 0), Input::Reveal(0, 0)]);
+        assert!(matches!(state.board().play_state, PlayState::Lost(_)));
+        let before = state.board().grid.snapshot();
+        for x in 0..4 {
+            let (left, top) = pixel(&state, x, 0);
+            for &button in [MouseButton::Left, MouseButton::Right, MouseButton::Middle].iter() {
+                state.click(secs(5), button, left, top);
+            }
+        }
+        assert!(state.board().grid.snapshot() == before);
+        assert_eq!(state.board().inputs.len(), 2);
+    }
+
+    #[cfg(feature = "debug")]
+    #[test]
+    fn revealing_mines_leaves_the_game_running() {
+        let mut state = game(2, Grid::with_mines(5, 1, &[(0, 0), (4, 0)]));
+        play(&mut state, &[Input::Reveal(1, 0), Input::RevealMines]);
+        assert!(state.board().grid.get(0, 0).unwrap().is_exposed());
+        assert!(state.board().grid.get(4, 0).unwrap().is_exposed());
+        assert!(matches!(state.board().play_state, PlayState::Playing(_)));
+    }
+
+    #[test]
+    fn middle_clicks_chord() {
+        let mut state = game(1, Grid::with_mines(4, 2, &[(1, 0)]));
+        play(&mut state, &[Input::Reveal(0, 1), Input::Mark(1, 0)]);
+        let (x, y) = pixel(&state, 0, 1);
+        state.click(secs(3), MouseButton::Middle, x, y);
+        assert_eq!(
+            state.board().inputs.last().unwrap().input,
+            Input::Chord(0, 1)
+        );
+        assert!(state.board().grid.get(0, 0).unwrap().is_exposed());
+        assert!(state.board().grid.get(1, 1).unwrap().is_exposed());
+        assert!(state.board().grid.get(2, 1).unwrap().is_covered());
+    }
+
+    #[test]
+    fn clicks_follow_the_ui_scale() {
+        let mut state = game(10, Grid::new(9, 9));
+        state.ui_scale = 2.;
+        // The board's top left corner sits at (92, 24) before scaling
+        assert_eq!(state.screen_to_grid(184., 48.), Some((0, 0)));
+        assert_eq!(state.screen_to_grid(200., 80.), Some((1, 2)));
+        state.ui_scale = 4.;
+        assert_eq!(state.screen_to_grid(184., 48.), None);
+        assert_eq!(state.screen_to_grid(400., 160.), Some((1, 2)));
+
+        let sprites = SpriteConfig::default();
+        assert_eq!(sprites.window_size(&state.board().grid, 2.), (512., 192.));
+        assert_eq!(sprites.window_size(&state.board().grid, 4.), (1024., 384.));
+    }
+
+    #[test]
+    fn hovering_off_the_board_clears_the_highlight() {
+        let mut state = game(10, Grid::new(9, 9));
+        let (x, y) = pixel(&state, 3, 5);
+        state.hover(secs(1), x, y);
+        assert_eq!(state.hovered, Some((3, 5)));
+        // The header, the margin beside the board and past its bottom edge
+        for &(x, y) in [(400., 50.), (100., 200.), (400., 400.)].iter() {
+            state.hover(secs(1), x, y);
+            assert_eq!(state.hovered, None);
+        }
+    }
+
+    #[test]
+    fn winning_flags_every_mine() {
+        let mut state = game(2, Grid::with_mines(5, 1, &[(0, 0), (4, 0)]));
+        play(&mut state, &[Input::Reveal(2, 0)]);
+        assert!(matches!(state.board().play_state, PlayState::Won(_)));
+        assert!(state.board().grid.get(0, 0).unwrap().is_flagged());
+        assert!(state.board().grid.get(4, 0).unwrap().is_flagged());
+        assert_eq!(state.board().total_flags, state.total_mines);
+    }
+
+    #[test]
+    fn flags_taken_back_still_count_as_used() {
+        let grid = Grid::with_mines(6, 1, &[(0, 0), (5, 0)]);
+        let mut state = game(2, grid.clone());
+        play(&mut state, &[Input::Reveal(1, 0), Input::Reveal(3, 0)]);
+        assert!(state.board().was_flagless());
+
+        // Flagged, questioned and back to covered before the win
+        let mut state = game(2, grid);
+        play(
+            &mut state,
+            &[
+                Input::Reveal(1, 0),
+                Input::Mark(5, 0),
+                Input::Mark(5, 0),
+                Input::Mark(5, 0),
+                Input::Reveal(3, 0),
+            ],
+        );
+        assert!(matches!(state.board().play_state, PlayState::Won(_)));
+        assert!(!state.board().was_flagless());
+    }
+
+    #[test]
+    fn window_title_follows_the_play_state() {
+        assert_eq!(
+            status_text(PlayState::Playing(secs(0)), secs(42), 10),
+            "00:42 \u{2014} 10 mines left"
+        );
+        assert_eq!(
+            status_text(PlayState::Unstarted, secs(0), 99),
+            "00:00 \u{2014} 99 mines left"
+        );
+        let paused = PlayState::Paused { elapsed: secs(5) };
+        assert_eq!(status_text(paused, secs(5), 3), "Paused");
+        assert_eq!(status_text(PlayState::Won(secs(9)), secs(9), 0), "You Win!");
+        assert_eq!(
+            status_text(PlayState::Lost(secs(9)), secs(9), 4),
+            "Game Over"
+        );
+    }
+
+    #[test]
+    fn training_catches_mine_clicks() {
+        let grid = Grid::with_mines(4, 1, &[(0, 0)]);
+        let mut state = game(1, grid.clone());
+        state.training = true;
+        play(&mut state, &[Input::Reveal(1, 0), Input::Reveal(0, 0)]);
+        assert!(matches!(state.board().play_state, PlayState::Playing(_)));
+        assert_eq!(state.board().mistakes, 1);
+        assert_eq!(state.board().warning_frames, WARNING_FRAMES);
+        assert!(state.board().grid.get(0, 0).unwrap().is_covered());
+
+        let mut state = game(1, grid);
+        play(&mut state, &[Input::Reveal(1, 0), Input::Reveal(0, 0)]);
+        assert!(matches!(state.board().play_state, PlayState::Lost(_)));
+        assert_eq!(state.board().mistakes, 0);
+    }
+
+    #[test]
+    fn the_timer_stops_at_its_cap() {
+        let mut state = game(1, Grid::new(9, 9));
+        state.board_mut().play_state = PlayState::Playing(secs(0));
+        assert_eq!(state.board().display_seconds(secs(65)), 65);
+        assert_eq!(state.board().formatted_time(secs(65)), "01:05");
+        let cap = MAX_DISPLAY_SECONDS as u64;
+        assert_eq!(
+            state.board().display_seconds(secs(cap)),
+            MAX_DISPLAY_SECONDS
+        );
+        assert_eq!(state.board().formatted_time(secs(cap)), "99:59");
+        assert_eq!(
+            state.board().display_seconds(secs(cap + 500)),
+            MAX_DISPLAY_SECONDS
+        );
+        assert_eq!(state.board().formatted_time(secs(cap + 500)), "99:59");
+        // The full time is kept for scoring
+        assert_eq!(state.elapsed(secs(cap + 500)), secs(cap + 500));
+    }
+
+    #[test]
+    fn auto_flag_marks_forced_mines() {
+        // * 2 * . .
+        let grid = Grid::with_mines(5, 1, &[(0, 0), (2, 0)]);
+        let mut state = game(2, grid.clone());
+        play(&mut state, &[Input::AutoFlag(true), Input::Reveal(1, 0)]);
+        assert!(state.board().grid.get(0, 0).unwrap().is_flagged());
+        assert!(state.board().grid.get(2, 0).unwrap().is_flagged());
+        assert_eq!(state.board().total_flags, 2);
+        assert!(state.board().grid.get(3, 0).unwrap().is_covered());
+
+        let mut state = game(2, grid);
+        play(&mut state, &[Input::Reveal(1, 0)]);
+        assert_eq!(state.board().total_flags, 0);
+    }
+
+    #[test]
+    fn clicks_follow_the_sprite_size() {
+        let mut state = game(10, Grid::new(9, 9));
+        state.ui_scale = 1.;
+        state.sprites = SpriteConfig {
+            cell_px: 16.,
+            header_px: 48.,
+        };
+        // Twice the default size, so the corner moves from (92, 24) to (184, 48)
+        assert_eq!(state.screen_to_grid(184., 48.), Some((0, 0)));
+        assert_eq!(state.screen_to_grid(205., 85.), Some((1, 2)));
+        assert_eq!(state.screen_to_grid(183., 60.), None);
+        assert_eq!(state.screen_to_grid(190., 47.), None);
+        assert_eq!(
+            state.sprites.window_size(&state.board().grid, 1.),
+            (512., 192.)
+        );
+    }
+
+    #[test]
+    fn the_camera_stays_on_the_board() {
+        // Shows 40 by 24 of the 60 by 30 board
+        let mut state = game(10, Grid::new(60, 30));
+        state.board_mut().pan(-5, -5);
+        assert_eq!(state.board().camera, (0, 0));
+        state.board_mut().pan(100, 100);
+        assert_eq!(state.board().camera, (20, 6));
+        // The top left corner of the view is now cell (20, 6)
+        assert_eq!(state.screen_to_grid(4., 100.), Some((20, 6)));
+        assert_eq!(state.screen_to_grid(4. + 32. * 39., 100.), Some((59, 6)));
+
+        state.board_mut().scroll_to(0, 10);
+        assert_eq!(state.board().camera, (0, 6));
+        state.board_mut().scroll_to(45, 2);
+        assert_eq!(state.board().camera, (6, 2));
+        assert_eq!(state.screen_to_grid(4., 100.), Some((6, 2)));
+    }
+
+    #[test]
+    fn times_show_minutes_and_seconds() {
+        assert_eq!(format_time(0), "00:00");
+        assert_eq!(format_time(5), "00:05");
+        assert_eq!(format_time(65), "01:05");
+        assert_eq!(format_time(3661), "61:01");
+    }
+
+    #[test]
+    fn each_win_condition_wins_its_own_way() {
+        let grid = Grid::with_mines(5, 1, &[(0, 0)]);
+        let flag_mine = [Input::Reveal(1, 0), Input::Mark(0, 0)];
+
+        // Flagging the mine isn't enough when every safe cell must be uncovered
+        let mut state = game(1, grid.clone());
+        play(&mut state, &flag_mine);
+        assert!(matches!(state.board().play_state, PlayState::Playing(_)));
+        play(&mut state, &[Input::Reveal(3, 0)]);
+        assert!(matches!(state.board().play_state, PlayState::Won(_)));
+
+        let mut state = game(1, grid.clone());
+        state.win_condition = WinCondition::FlagAllMines;
+        play(&mut state, &flag_mine);
+        assert!(matches!(state.board().play_state, PlayState::Won(_)));
+
+        // A flag on a safe cell holds the win back until it's taken off
+        let mut state = game(1, grid.clone());
+        state.win_condition = WinCondition::FlagAllMines;
+        play(&mut state, &[Input::Reveal(1, 0), Input::Mark(4, 0)]);
+        play(&mut state, &[Input::Mark(0, 0)]);
+        assert!(matches!(state.board().play_state, PlayState::Playing(_)));
+        play(&mut state, &[Input::Mark(4, 0), Input::Mark(4, 0)]);
+        assert!(matches!(state.board().play_state, PlayState::Won(_)));
+
+        // Uncovering every safe cell still wins
+        let mut state = game(1, grid);
+        state.win_condition = WinCondition::FlagAllMines;
+        play(&mut state, &[Input::Reveal(3, 0)]);
+        assert!(matches!(state.board().play_state, PlayState::Won(_)));
+    }
+
+    /** A beginner board with a number uncovered, saved mid-game */
+    fn beginner_in_progress() -> GameState {
+        let mut mines = (0..9).map(|x| (x, 0)).collect::<Vec<(i32, i32)>>();
+        mines.push((0, 8));
+        let mut state = game(10, Grid::with_mines(9, 9, &mines));
+        state.scores = Scores::default();
+        play(&mut state, &[Input::Reveal(0, 1)]);
+        assert!(matches!(state.board().play_state, PlayState::Playing(_)));
+        state
+    }
+
+    #[cfg(not(feature = "serde"))]
+    #[test]
+    fn resumed_text_saves_set_no_best_time() {
+        let saved = beginner_in_progress().board().grid.serialize();
+        let mut state = game(10, Grid::new(9, 9));
+        state.scores = Scores::default();
+        state.apply_text(secs(600), &saved).unwrap();
+        play(&mut state, &[Input::Reveal(4, 4)]);
+        assert!(matches!(state.board().play_state, PlayState::Won(_)));
+        assert_eq!(state.scores.best(Difficulty::Beginner), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_saves_keep_assistance() {
+        let mut state = beginner_in_progress();
+        state.board_mut().assisted = true;
+        let json = state.to_json(secs(30)).unwrap();
+        let mut loaded = game(10, Grid::new(9, 9));
+        loaded.scores = Scores::default();
+        loaded.apply_json(&json).unwrap();
+        assert!(loaded.board().assisted);
+        loaded.toggle_pause(secs(40));
+        play(&mut loaded, &[Input::Reveal(4, 4)]);
+        assert!(matches!(loaded.board().play_state, PlayState::Won(_)));
+        assert_eq!(loaded.scores.best(Difficulty::Beginner), None);
+    }
+
+    #[test]
+    fn solver_and_auto_flag_moves_are_assisted() {
+        // * 2 * . .
+        let grid = Grid::with_mines(5, 1, &[(0, 0), (2, 0)]);
+        let mut state = game(2, grid.clone());
+        play(&mut state, &[Input::Reveal(1, 0)]);
+        assert!(!state.board().assisted);
+        play(&mut state, &[Input::SolveStep]);
+        assert!(state.board().assisted);
+
+        let mut state = game(2, grid);
+        play(&mut state, &[Input::AutoFlag(true), Input::Reveal(1, 0)]);
+        assert!(state.board().assisted);
+    }
+
+    #[test]
+    fn solver_steps_are_turns_that_training_can_catch() {
+        // * 2 * . .
+        let mut state = game(2, Grid::with_mines(5, 1, &[(0, 0), (2, 0)]));
+        play(&mut state, &[Input::Reveal(1, 0), Input::SolveStep]);
+        assert_eq!(state.board().total_flags, 2);
+        assert_eq!(state.board().turns, 2);
+        // Nothing left to deduce, so no turn is taken
+        play(&mut state, &[Input::SolveStep]);
+        assert_eq!(state.board().turns, 2);
+
+        // A wrong flag has the solver uncover the mine
+        let grid = Grid::with_mines(4, 1, &[(0, 0)]);
+        let steps = [Input::Reveal(1, 0), Input::Mark(2, 0), Input::SolveStep];
+        let mut state = game(1, grid.clone());
+        state.training = true;
+        play(&mut state, &steps);
+        assert!(matches!(state.board().play_state, PlayState::Playing(_)));
+        assert_eq!(state.board().mistakes, 1);
+        assert!(state.board().grid.get(0, 0).unwrap().is_covered());
+
+        let mut state = game(1, grid);
+        play(&mut state, &steps);
+        assert!(matches!(state.board().play_state, PlayState::Lost(_)));
+    }
+
+    #[test]
+    fn marks_survive_a_no_guess_first_click() {
+        let mut state = game(10, Grid::new(9, 9));
+        state.no_guess = true;
+        play(
+            &mut state,
+            &[Input::Mark(8, 8), Input::Mark(0, 8), Input::Mark(0, 8)],
+        );
+        play(&mut state, &[Input::Reveal(4, 4)]);
+        assert!(state.board().grid.get(8, 8).unwrap().is_flagged());
+        assert!(state.board().grid.get(0, 8).unwrap().is_questioned());
+        assert_eq!(state.board().total_flags, 1);
+
+        // Back through the question mark, then the flag, then the first flag
+        play(&mut state, &[Input::Undo, Input::Undo]);
+        assert!(state.board().grid.get(0, 8).unwrap().is_covered());
+        assert!(state.board().grid.get(8, 8).unwrap().is_flagged());
+        play(&mut state, &[Input::Undo]);
+        assert!(state.board().grid.get(8, 8).unwrap().is_covered());
+        assert_eq!(state.board().total_flags, 0);
+    }
+
+    #[test]
+    fn summaries_read_like_the_header() {
+        let mut state = game(1, Grid::with_mines(5, 1, &[(0, 0)]));
+        state.apply_input(secs(1), Input::Reveal(1, 0));
+        state.apply_input(secs(126), Input::Reveal(3, 0));
+        assert!(matches!(state.board().play_state, PlayState::Won(_)));
+        let texts = state
+            .summary_rows(state.board())
+            .into_iter()
+            .map(|(_, _, text)| text)
+            .collect::<Vec<String>>();
+        let efficiency = format!("{}%", (state.board().efficiency() * 100.).round());
+        assert_eq!(texts, vec!["02:05", "2", "1", "1", efficiency.as_str()]);
+        assert_eq!(text_to_sprites("87%"), vec![8, 7, PERCENT]);
+    }
+
+    #[test]
+    fn racers_are_dealt_the_same_board() {
+        let deal = || {
+            let mut state = game(40, Grid::new(16, 16));
+            state.race(2);
+            state
+                .boards
+                .iter()
+                .map(|board| (board.grid.mine_positions(), board.grid.snapshot()))
+                .collect::<Vec<_>>()
+        };
+        let boards = deal();
+        assert_eq!(boards.len(), 2);
+        assert_eq!(boards[0].0.len(), 40);
+        // The middle is already open, so nobody's first click is a guess
+        let middle = boards[0].1.iter().find(|cell| (cell.x, cell.y) == (8, 8));
+        assert!(middle.is_some_and(|cell| cell.state == CellState::Exposed));
+        assert!(boards
+            .iter()
+            .chain(deal().iter())
+            .all(|board| *board == boards[0]));
+    }
+
+    #[test]
+    fn racers_are_timed_on_one_clock() {
+        let mut state = game(1, Grid::with_mines(3, 1, &[(0, 0)]));
+        state.race(2);
+        let (width, _) = state.board_size();
+        let click = |state: &mut GameState, now: u64, button, player: usize, x: i32| {
+            let (left, top) = pixel(state, x, 0);
+            state.click(secs(now), button, left + player as f32 * width, top);
+            // Let go of the button, as the window would
+            state.drag_flagging = false;
+        };
+        // The second player's flag starts both clocks
+        click(&mut state, 2, MouseButton::Right, 1, 1);
+        assert_eq!(state.active, 1);
+        click(&mut state, 5, MouseButton::Left, 0, 2);
+        click(&mut state, 9, MouseButton::Left, 1, 0);
+        assert!(state.boards[0].play_state == PlayState::Won(secs(3)));
+        assert!(state.boards[1].play_state == PlayState::Lost(secs(7)));
+
+        // A finished player can't keep playing
+        click(&mut state, 10, MouseButton::Right, 0, 0);
+        assert!(state.boards[0].grid.get(0, 0).unwrap().is_flagged());
+        state.key_action(secs(11), KeyAction::Restart);
+        assert!(state
+            .boards
+            .iter()
+            .all(|board| board.play_state == PlayState::Unstarted));
+    }
 }
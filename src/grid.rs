@@ -1,25 +1,63 @@
-use ggez::{
-    conf::{WindowMode, WindowSetup},
-    event,
-    graphics::{FilterMode, Image},
-    Context, ContextBuilder, GameError,
-};
+use rand::{seq::SliceRandom, Rng};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use std::{collections::VecDeque, error::Error, fmt, time::Duration};
 
-use std::{fmt, path::Path, time::Duration};
+use crate::{
+    base64,
+    solver::{self, Action},
+};
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum BoardState {
     InProgress,
     Cleared,
     Detonated,
 }
 
+impl BoardState {
+    /** Whether the game is over, won or lost */
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, BoardState::Cleared | BoardState::Detonated)
+    }
+}
+
+/** A minefield. Clones own all of their cells, so a copy can be played
+ahead speculatively without touching the original */
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Grid {
     cells: Vec<Cell>,
     pub width: i32,
     pub height: i32,
+    mine_count: i32,
+    /** Kept in step with every uncover so winning can be checked without
+    scanning the board */
+    exposed_safe_cells: i32,
+    /** Opposite edges are neighbors, making the board a torus */
+    #[cfg_attr(feature = "serde", serde(default))]
+    wrap: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    topology: Topology,
+    #[cfg_attr(feature = "serde", serde(default))]
+    neighbor_mode: NeighborMode,
+    /** Time into the game that newly uncovered cells get stamped with */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    clock: Duration,
 }
 
-const NEIGHBOR_OFFSETS: [(i32, i32); 8] = [
+/** How many boards `generate_no_guess` tries before settling for one that
+may need a guess. Expert boards rarely take more than a hundred */
+const NO_GUESS_ATTEMPTS: usize = 200;
+
+/** Longest side a board read from a save or puzzle code may have, so a bad
+header can't ask for more memory than any game would use */
+pub const MAX_SIDE: i32 = 1000;
+
+/** Neighbors on a square board: all eight surrounding cells */
+const SQUARE_OFFSETS: [(i32, i32); 8] = [
     (-1, -1),
     (0, -1),
     (1, -1),
@@ -30,12 +68,52 @@ const NEIGHBOR_OFFSETS: [(i32, i32); 8] = [
     (-1, 0),
 ];
 
+/** Neighbors in `NeighborMode::Four`: the four sharing an edge on a square
+board, and on a hex board the two in the same row and the two straight
+above and below in storage order */
+const ORTHOGONAL_OFFSETS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+/** Neighbors on a hex board, where odd rows sit half a cell to the right of
+even ones */
+const HEX_EVEN_ROW_OFFSETS: [(i32, i32); 6] = [(-1, -1), (0, -1), (-1, 0), (1, 0), (-1, 1), (0, 1)];
+const HEX_ODD_ROW_OFFSETS: [(i32, i32); 6] = [(0, -1), (1, -1), (-1, 0), (1, 0), (0, 1), (1, 1)];
+
+/** How cells are laid out */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Topology {
+    #[default]
+    Square,
+    Hex,
+}
+
+/** Which of a cell's adjacent cells count as neighbors, for placement,
+counts and flood fill alike */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum NeighborMode {
+    /** Every adjacent cell: eight on a square board, six on a hex one */
+    #[default]
+    Eight,
+    /** Only the four cells from `ORTHOGONAL_OFFSETS`, so diagonal mines
+    don't count */
+    Four,
+}
+
 impl Grid {
+    /** Doesn't check the dimensions, so a zero or negative side gives a
+    board with no cells. Use `try_new` for sizes that come from outside */
     pub fn new(width: i32, height: i32) -> Self {
         let mut grid = Grid {
             cells: vec![],
             width,
             height,
+            mine_count: 0,
+            exposed_safe_cells: 0,
+            wrap: false,
+            topology: Topology::Square,
+            neighbor_mode: NeighborMode::Eight,
+            clock: Duration::default(),
         };
         for y in 0..height {
             for x in 0..width {
@@ -46,41 +124,237 @@ impl Grid {
         grid
     }
 
+    /** A board whose edges wrap around to the opposite side */
+    pub fn new_wrapping(width: i32, height: i32) -> Self {
+        Grid {
+            wrap: true,
+            ..Grid::new(width, height)
+        }
+    }
+
+    pub fn wraps(&self) -> bool {
+        self.wrap
+    }
+
+    /** Turns wrapping on or off, recounting every cell's neighboring mines */
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+        self.recompute_counts();
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /** Switches how cells connect, recounting every cell's neighboring mines */
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+        self.recompute_counts();
+    }
+
+    pub fn neighbor_mode(&self) -> NeighborMode {
+        self.neighbor_mode
+    }
+
+    /** Switches which adjacent cells count as neighbors, recounting every
+    cell's neighboring mines */
+    pub fn set_neighbor_mode(&mut self, neighbor_mode: NeighborMode) {
+        self.neighbor_mode = neighbor_mode;
+        self.recompute_counts();
+    }
+
+    /** Offsets from a cell in row `y` to each of its neighbors */
+    pub fn neighbor_offsets(&self, y: i32) -> &'static [(i32, i32)] {
+        match (self.neighbor_mode, self.topology) {
+            (NeighborMode::Four, _) => &ORTHOGONAL_OFFSETS,
+            (NeighborMode::Eight, Topology::Square) => &SQUARE_OFFSETS,
+            (NeighborMode::Eight, Topology::Hex) if y.rem_euclid(2) == 0 => &HEX_EVEN_ROW_OFFSETS,
+            (NeighborMode::Eight, Topology::Hex) => &HEX_ODD_ROW_OFFSETS,
+        }
+    }
+
+    /** A board with mines at exactly the given coords. Repeats are placed
+    once, and out of bounds coords panic */
+    pub fn with_mines(width: i32, height: i32, mines: &[(i32, i32)]) -> Grid {
+        let mut grid = Grid::new(width, height);
+        for &(x, y) in mines {
+            grid.place_mine(x, y);
+        }
+        grid
+    }
+
+    /** An empty board, checked to have at least one row and column */
+    pub fn try_new(width: i32, height: i32) -> Result<Grid, GridError> {
+        if width <= 0 || height <= 0 {
+            return Err(GridError::InvalidDimensions { width, height });
+        }
+        Ok(Grid::new(width, height))
+    }
+
+    /** An empty board, checked to have room for `mine_count` mines while
+    keeping at least one cell safe */
+    pub fn try_with_mines(width: i32, height: i32, mine_count: i32) -> Result<Grid, GridError> {
+        let grid = Grid::try_new(width, height)?;
+        let capacity = (width * height - 1).max(0);
+        if mine_count > capacity {
+            return Err(GridError::TooManyMines {
+                requested: mine_count,
+                capacity,
+            });
+        }
+        Ok(grid)
+    }
+
+    /** Out of bounds coords and flagged cells are ignored and leave the board
+    `InProgress` */
     pub fn uncover(&mut self, x: i32, y: i32) -> BoardState {
-        let index = self.coord_to_index(x, y).unwrap();
-        self.cells[index].state = CellState::Exposed;
+        self.uncover_ordered(x, y).0
+    }
+
+    /** Uncovers each coord in turn, stopping at the first mine. Returns the
+    state after the last uncover */
+    pub fn uncover_many(&mut self, coords: &[(i32, i32)]) -> BoardState {
+        let mut board_state = BoardState::InProgress;
+        for &(x, y) in coords {
+            board_state = self.uncover(x, y);
+            if board_state.is_terminal() {
+                break;
+            }
+        }
+        board_state
+    }
+
+    /** Like `uncover`, but also returns the newly exposed cells in the order
+    the flood fill reached them */
+    pub fn uncover_ordered(&mut self, x: i32, y: i32) -> (BoardState, Vec<(i32, i32)>) {
+        self.uncover_to_depth(x, y, None)
+    }
+
+    /** Like `uncover_ordered`, but the flood fill stops `reveal_depth` steps
+    out from (x, y), leaving the rest of the opening covered. `None` floods
+    the whole opening */
+    pub fn uncover_to_depth(
+        &mut self,
+        x: i32,
+        y: i32,
+        reveal_depth: Option<u32>,
+    ) -> (BoardState, Vec<(i32, i32)>) {
+        let mut exposed = vec![];
+        let index = match self.coord_to_index(x, y) {
+            Some(index) => index,
+            None => return (BoardState::InProgress, exposed),
+        };
+        if self.cells[index].is_flagged() {
+            return (BoardState::InProgress, exposed);
+        }
+        if self.cells[index].has_mine {
+            self.cells[index].state = CellState::Detonated;
+            self.cells[index].revealed_at = Some(self.clock);
+            self.reveal_on_loss();
+            return (BoardState::Detonated, exposed);
+        }
 
-        if self.cells[index].has_mine == true {
-            self.uncover_bombs();
-            return BoardState::Detonated;
+        if self.cells[index].state != CellState::Exposed {
+            self.exposed_safe_cells += 1;
+            self.cells[index].revealed_at = Some(self.clock);
+            exposed.push((x, y));
         }
+        self.cells[index].state = CellState::Exposed;
 
         // if the cell has no adjacent mines, uncover adjacent cells without adjacent mines
-        if self.cells[index].neighboring_mines == 0 && self.cells[index].has_mine == false {
-            for (i, j) in NEIGHBOR_OFFSETS.iter() {
-                self.coord_to_index(x + i, y + j)
-                    .and_then(|neighbor_index| {
-                        Some(
-                            if self.cells[neighbor_index].state == CellState::Covered
-                                && self.cells[neighbor_index].has_mine == false
-                            {
-                                self.uncover(x + i, y + j);
-                            },
-                        )
-                    });
+        // Each pass reveals one step further out
+        let mut frontier = VecDeque::from([(x, y)]);
+        let mut depth = 0;
+        while !frontier.is_empty() && reveal_depth.is_none_or(|limit| depth < limit) {
+            let mut next = VecDeque::new();
+            for (x, y) in frontier {
+                self.reveal_safe_neighbors(x, y, &mut next);
             }
+            exposed.extend(next.iter().copied());
+            frontier = next;
+            depth += 1;
         }
-        BoardState::InProgress
+
+        let board_state = if self.is_won() {
+            BoardState::Cleared
+        } else {
+            BoardState::InProgress
+        };
+        (board_state, exposed)
     }
 
-    pub fn toggle_flag(&mut self, x: i32, y: i32) -> i32 {
+    /** One step of the flood fill: if (x, y) has no adjacent mines, exposes
+    its covered safe neighbors and queues each to be stepped from in turn */
+    fn reveal_safe_neighbors(&mut self, x: i32, y: i32, queue: &mut VecDeque<(i32, i32)>) {
         let index = self.coord_to_index(x, y).unwrap();
+        if self.cells[index].neighboring_mines != 0 {
+            return;
+        }
+        for neighbor in self.neighbor_coords(x, y) {
+            let neighbor_index = self.coord_to_index(neighbor.0, neighbor.1).unwrap();
+            let cell = &mut self.cells[neighbor_index];
+            if cell.is_covered() && !cell.has_mine {
+                cell.state = CellState::Exposed;
+                cell.revealed_at = Some(self.clock);
+                self.exposed_safe_cells += 1;
+                queue.push_back(neighbor);
+            }
+        }
+    }
+
+    /** Uncovers the covered neighbors of an exposed number once it has as
+    many adjacent flags as adjacent mines. Question marks neither count as
+    flags nor get uncovered */
+    pub fn chord(&mut self, x: i32, y: i32) -> BoardState {
+        if !self.is_number_satisfied(x, y) {
+            return BoardState::InProgress;
+        }
+
+        let mut board_state = BoardState::InProgress;
+        for (x, y) in self.neighbor_coords(x, y) {
+            if self.cells[self.coord_to_index(x, y).unwrap()].is_covered() {
+                board_state = self.uncover(x, y);
+                if let BoardState::Detonated = board_state {
+                    return board_state;
+                }
+            }
+        }
+        board_state
+    }
+
+    /** Cycles Covered -> Flagged -> Questioned -> Covered, returning the
+    change in the number of flags. Out of bounds coords are ignored */
+    pub fn cycle_mark(&mut self, x: i32, y: i32) -> i32 {
+        let index = match self.coord_to_index(x, y) {
+            Some(index) => index,
+            None => return 0,
+        };
         match self.cells[index].state {
+            CellState::Covered => {
+                self.cells[index].state = CellState::Flagged;
+                1
+            }
             CellState::Flagged => {
-                self.cells[index].state = CellState::Covered;
+                self.cells[index].state = CellState::Questioned;
                 -1
             }
-            CellState::Covered => {
+            CellState::Questioned => {
+                self.cells[index].state = CellState::Covered;
+                0
+            }
+            _ => 0,
+        }
+    }
+
+    /** Flags a covered or questioned cell, returning the change in the
+    number of flags */
+    pub fn flag(&mut self, x: i32, y: i32) -> i32 {
+        let index = match self.coord_to_index(x, y) {
+            Some(index) => index,
+            None => return 0,
+        };
+        match self.cells[index].state {
+            CellState::Covered | CellState::Questioned => {
                 self.cells[index].state = CellState::Flagged;
                 1
             }
@@ -88,146 +362,2042 @@ impl Grid {
         }
     }
 
+    /** Puts a mark back to an earlier `Covered`, `Flagged` or `Questioned`
+    state, returning the change in the number of flags. Cells that have
+    since been uncovered are left alone */
+    pub fn restore_mark(&mut self, x: i32, y: i32, state: CellState) -> i32 {
+        let index = match self.coord_to_index(x, y) {
+            Some(index) => index,
+            None => return 0,
+        };
+        let is_mark = |state: CellState| {
+            matches!(
+                state,
+                CellState::Covered | CellState::Flagged | CellState::Questioned
+            )
+        };
+        let current = self.cells[index].state;
+        if !is_mark(current) || !is_mark(state) {
+            return 0;
+        }
+        self.cells[index].state = state;
+        (state == CellState::Flagged) as i32 - (current == CellState::Flagged) as i32
+    }
+
+    /** Sets how far into the game it is, for stamping cells as they're
+    uncovered */
+    pub fn set_clock(&mut self, elapsed: Duration) {
+        self.clock = elapsed;
+    }
+
+    /** Every uncovered cell in the order the player reached it. Cells opened
+    by the same click share a time and are listed in row-major order */
+    pub fn reveal_timeline(&self) -> Vec<&Cell> {
+        let mut revealed = self
+            .cells
+            .iter()
+            .filter(|cell| cell.revealed_at.is_some())
+            .collect::<Vec<&Cell>>();
+        revealed.sort_by_key(|cell| cell.revealed_at);
+        revealed
+    }
+
+    /** Flags every unflagged mine, returning the change in the number of
+    flags. Used to fill in the flags once the board is cleared */
+    pub fn flag_all_mines(&mut self) -> i32 {
+        let mut flagged = 0;
+        for cell in self.cells.iter_mut() {
+            if cell.has_mine && (cell.is_covered() || cell.is_questioned()) {
+                cell.state = CellState::Flagged;
+                flagged += 1;
+            }
+        }
+        flagged
+    }
+
+    /** Exposes every mine that isn't flagged or already detonated */
     pub fn uncover_bombs(&mut self) {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let index = self.coord_to_index(x, y).unwrap();
-                if self.cells[index].has_mine == true {
-                    self.cells[index].state = CellState::Exposed;
-                }
+        for cell in self.cells.iter_mut() {
+            if cell.has_mine && (cell.is_covered() || cell.is_questioned()) {
+                cell.state = CellState::Exposed;
+            }
+        }
+    }
+
+    /** Exposes the remaining mines and marks any flags that were wrong */
+    pub fn reveal_on_loss(&mut self) {
+        self.uncover_bombs();
+        for cell in self.cells.iter_mut() {
+            if cell.is_flagged() && !cell.has_mine {
+                cell.state = CellState::WronglyFlagged;
             }
         }
     }
 
+    /** Every cell with its coords, in row-major order */
+    pub fn iter_cells(&self) -> impl Iterator<Item = (i32, i32, &Cell)> {
+        self.cells.iter().map(|cell| (cell.x, cell.y, cell))
+    }
+
+    /** Like `iter_cells`, but changing `has_mine` through it leaves mine
+    counts stale until `recompute_counts` is called */
+    pub fn iter_mut_cells(&mut self) -> impl Iterator<Item = (i32, i32, &mut Cell)> {
+        self.cells.iter_mut().map(|cell| (cell.x, cell.y, cell))
+    }
+
+    /** The board as a player sees it, in row-major order */
+    pub fn snapshot(&self) -> Vec<CellView> {
+        self.cells
+            .iter()
+            .map(|cell| {
+                let exposed = match cell.state {
+                    CellState::Exposed | CellState::Detonated | CellState::WronglyFlagged => true,
+                    CellState::Covered | CellState::Flagged | CellState::Questioned => false,
+                };
+                CellView {
+                    x: cell.x,
+                    y: cell.y,
+                    state: cell.state,
+                    neighboring_mines: if exposed {
+                        Some(cell.neighboring_mines)
+                    } else {
+                        None
+                    },
+                    has_mine: if exposed { Some(cell.has_mine) } else { None },
+                }
+            })
+            .collect()
+    }
+
     pub fn get(&self, x: i32, y: i32) -> Option<Cell> {
         let index = self.coord_to_index(x, y)?;
         Some(self.cells[index].clone())
     }
 
-    pub fn get_neighbors(&self, x: i32, y: i32) -> Vec<&Cell> {
-        let mut neighbors = vec![];
-        for (i, j) in NEIGHBOR_OFFSETS.iter() {
-            if x == 0 && y == 0 {
-                continue;
+    /** Positions of the cell's neighbors, wrapped onto the board when it
+    wraps. The cell itself and repeats (on boards narrower than three cells
+    with wrapping on) are left out */
+    pub fn neighbor_coords(&self, x: i32, y: i32) -> Vec<(i32, i32)> {
+        let origin = self.coord_to_index(x, y);
+        let mut coords = vec![];
+        for (i, j) in self.neighbor_offsets(y).iter() {
+            if let Some(index) = self.coord_to_index(x + i, y + j) {
+                let coord = self.index_to_coord(index).unwrap();
+                if Some(index) != origin && !coords.contains(&coord) {
+                    coords.push(coord);
+                }
             }
-            self.coord_to_index(x + i, y + j)
-                .and_then(|index| Some(neighbors.push(&self.cells[index])));
         }
-        neighbors
+        coords
     }
 
-    pub fn place_mine(&mut self, x: i32, y: i32) {
-        let index = self.coord_to_index(x, y).unwrap();
-        self.cells[index].has_mine = true;
-        self.cells[index].neighboring_mines = self
-            .get_neighbors(x, y)
-            .iter()
-            .filter(|cell| cell.has_mine)
-            .collect::<Vec<&&Cell>>()
-            .len() as u8;
+    pub fn get_neighbors(&self, x: i32, y: i32) -> Vec<&Cell> {
+        self.neighbor_coords(x, y)
+            .into_iter()
+            .map(|(x, y)| &self.cells[self.coord_to_index(x, y).unwrap()])
+            .collect()
+    }
 
-        // Update neighbor mine counts
-        for (i, j) in NEIGHBOR_OFFSETS.iter() {
-            if x == 0 && y == 0 {
-                continue;
+    /** Chords every satisfied number, over and over until a pass uncovers
+    nothing new. Stops as soon as a mine goes off or the board is cleared,
+    and reports a board that was already over as it stands */
+    pub fn auto_chord_all(&mut self) -> BoardState {
+        loop {
+            let exposed_before = self.exposed_safe_cells;
+            for index in 0..self.cells.len() {
+                let (x, y) = (self.cells[index].x, self.cells[index].y);
+                if !self.is_number_satisfied(x, y) {
+                    continue;
+                }
+                let board_state = self.chord(x, y);
+                if board_state.is_terminal() {
+                    return board_state;
+                }
+            }
+            if self.exposed_safe_cells == exposed_before {
+                return self.board_state();
             }
-            self.coord_to_index(x + i, y + j)
-                .and_then(|index| Some(self.cells[index].neighboring_mines += 1));
         }
     }
 
-    /** Returns `None` if coord is out of bounds */
-    fn coord_to_index(&self, x: i32, y: i32) -> Option<usize> {
-        if x >= 0 && x < self.width && y >= 0 && y < self.height {
-            Some((x + y * self.width) as usize)
+    /** Where the board stands, worked out from its cells rather than the
+    last move */
+    fn board_state(&self) -> BoardState {
+        if self
+            .cells
+            .iter()
+            .any(|cell| cell.state == CellState::Detonated)
+        {
+            BoardState::Detonated
+        } else if self.is_won() {
+            BoardState::Cleared
         } else {
-            None
+            BoardState::InProgress
         }
     }
-}
 
-impl fmt::Display for Grid {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            self.cells
+    /** Whether (x, y) is an exposed number with exactly as many flagged
+    neighbors as neighboring mines, which is when it can be chorded */
+    pub fn is_number_satisfied(&self, x: i32, y: i32) -> bool {
+        let cell = match self.coord_to_index(x, y) {
+            Some(index) => &self.cells[index],
+            None => return false,
+        };
+        cell.is_exposed()
+            && !cell.has_mine
+            && cell.neighboring_mines > 0
+            && self.neighboring_flags(x, y) == cell.neighboring_mines
+    }
+
+    /** How many neighbors of (x, y) are flagged. Question marks don't count */
+    pub fn neighboring_flags(&self, x: i32, y: i32) -> u8 {
+        self.get_neighbors(x, y)
+            .iter()
+            .filter(|cell| cell.is_flagged())
+            .count() as u8
+    }
+
+    /** Returns `false` if the cell already had a mine. Bumps the neighbor
+    count of every adjacent cell */
+    pub fn place_mine(&mut self, x: i32, y: i32) -> bool {
+        let index = self.coord_to_index(x, y).unwrap();
+        if self.cells[index].has_mine {
+            return false;
+        }
+        self.cells[index].has_mine = true;
+        self.mine_count += 1;
+        if self.cells[index].is_exposed() {
+            self.exposed_safe_cells -= 1;
+        }
+        for (x, y) in self.neighbor_coords(x, y) {
+            let neighbor_index = self.coord_to_index(x, y).unwrap();
+            self.cells[neighbor_index].neighboring_mines += 1;
+        }
+        true
+    }
+
+    /** The inverse of `place_mine`. Returns `false` if the cell had no mine */
+    pub fn remove_mine(&mut self, x: i32, y: i32) -> bool {
+        let index = self.coord_to_index(x, y).unwrap();
+        if !self.cells[index].has_mine {
+            return false;
+        }
+        self.cells[index].has_mine = false;
+        self.mine_count -= 1;
+        if self.cells[index].is_exposed() {
+            self.exposed_safe_cells += 1;
+        }
+        for (x, y) in self.neighbor_coords(x, y) {
+            let neighbor_index = self.coord_to_index(x, y).unwrap();
+            self.cells[neighbor_index].neighboring_mines -= 1;
+        }
+        true
+    }
+
+    /** Recounts the adjacent mines of every cell from scratch */
+    pub fn recompute_counts(&mut self) {
+        for index in 0..self.cells.len() {
+            let (x, y) = (self.cells[index].x, self.cells[index].y);
+            self.cells[index].neighboring_mines = self
+                .get_neighbors(x, y)
                 .iter()
-                .map(|cell| format!("{}", &cell))
-                .collect::<Vec<String>>()
-                .chunks(self.width as usize)
-                .map(|chunk| chunk.join(" "))
-                .collect::<Vec<String>>()
-                .join("\n")
-        )
+                .filter(|cell| cell.has_mine)
+                .count() as u8;
+        }
     }
-}
 
-#[derive(Clone)]
-pub struct Cell {
-    state: CellState,
-    pub has_mine: bool,
-    neighboring_mines: u8,
-    x: i32,
-    y: i32,
-}
+    /** Randomly places exactly `count` new mines, never on an excluded
+    coord. Panics if there aren't enough free cells to hold them */
+    pub fn populate_mines(&mut self, count: i32, exclude: &[(i32, i32)], rng: &mut impl Rng) {
+        self.place_random_mines(count as usize, exclude, rng);
+    }
 
-impl Cell {
-    fn new(state: CellState, has_mine: bool, neighboring_mines: u8, x: i32, y: i32) -> Self {
-        Cell {
-            state,
-            has_mine,
-            neighboring_mines,
-            x,
-            y,
+    /** Randomly places `count` new mines clear of `start` and its
+    neighbors, so uncovering it opens a region. Boards too dense for that
+    only keep `start` itself clear */
+    pub fn populate_around(&mut self, count: i32, start: (i32, i32), rng: &mut impl Rng) {
+        let exclude = self.start_area(count, start);
+        self.populate_mines(count, &exclude, rng);
+    }
+
+    /** The cells kept free of mines around a first click at `start` */
+    fn start_area(&self, count: i32, start: (i32, i32)) -> Vec<(i32, i32)> {
+        let (x, y) = start;
+        let mut area = self.neighbor_coords(x, y);
+        area.push(start);
+        let free = self.cells.iter().filter(|cell| !cell.has_mine).count();
+        if free < area.len() + count.max(0) as usize {
+            area = vec![start];
         }
+        area
     }
 
-    pub fn sprite_index(&self) -> usize {
-        match self.state {
-            CellState::Covered => 13,
-            CellState::Exposed => {
-                if self.has_mine {
-                    10
-                } else {
-                    if self.neighboring_mines == 0 {
-                        14
-                    } else {
-                        self.neighboring_mines as usize
+    /** Generates a board that the solver can clear from `start` without
+    guessing. `start` always lands on an opening. Falls back to an ordinary
+    board when none turns up within a fixed number of attempts */
+    pub fn generate_no_guess(
+        width: i32,
+        height: i32,
+        mines: i32,
+        start: (i32, i32),
+        rng: &mut impl Rng,
+    ) -> Grid {
+        let mut grid = Grid::new(width, height);
+        grid.populate_no_guess(mines, start, rng);
+        grid
+    }
+
+    /** Lays mines on an empty board the way `generate_no_guess` does,
+    keeping the board's size and wrapping. Returns `false` if it had to fall
+    back to a board that may need a guess */
+    pub fn populate_no_guess(&mut self, count: i32, start: (i32, i32), rng: &mut impl Rng) -> bool {
+        let (x, y) = start;
+        let exclude = self.start_area(count, start);
+        let empty = self.clone();
+        for _ in 0..NO_GUESS_ATTEMPTS {
+            *self = empty.clone();
+            self.populate_mines(count, &exclude, rng);
+            if self.solvable_from(x, y) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /** Whether the covered cell at (x, y) can be proven mine-free from what
+    the player can see, trusting every flag. Only exposed numbers and marks
+    are looked at, never where the mines really are. Chances estimated from
+    the overall density prove nothing, so only enumerated ones count */
+    pub fn is_provably_safe(&self, x: i32, y: i32) -> bool {
+        let covered = self
+            .get(x, y)
+            .is_some_and(|cell| matches!(cell.state, CellState::Covered | CellState::Questioned));
+        covered
+            && (solver::deduce(self).contains(&Action::Reveal(x, y))
+                || solver::enumerated(self).get(&(x, y)) == Some(&0.))
+    }
+
+    /** Whether repeatedly applying the solver's deductions clears the board
+    after uncovering `(x, y)`. When no single number settles anything, cells
+    that every fitting layout agrees on are used instead */
+    pub fn solvable_from(&self, x: i32, y: i32) -> bool {
+        let mut grid = self.clone();
+        let mut board_state = grid.uncover(x, y);
+        while !board_state.is_terminal() {
+            let mut actions = solver::deduce(&grid);
+            if actions.is_empty() {
+                actions = solver::enumerated(&grid)
+                    .into_iter()
+                    .filter_map(|((x, y), chance)| {
+                        if chance == 0. {
+                            Some(Action::Reveal(x, y))
+                        } else if chance == 1. {
+                            Some(Action::Flag(x, y))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect();
+            }
+            if actions.is_empty() {
+                return false;
+            }
+            for action in actions {
+                match action {
+                    Action::Flag(x, y) => {
+                        grid.flag(x, y);
                     }
+                    Action::Reveal(x, y) => board_state = grid.uncover(x, y),
+                }
+                if board_state.is_terminal() {
+                    break;
                 }
             }
-            CellState::Flagged => 11,
         }
+        board_state == BoardState::Cleared
     }
-}
 
-#[derive(Clone, PartialEq)]
-enum CellState {
-    Covered,
-    Exposed,
-    Flagged,
-}
+    /** Places exactly `count` distinct mines drawn from `rng` */
+    pub fn seed_mines(&mut self, count: u32, rng: &mut impl Rng) {
+        self.place_random_mines(count as usize, &[], rng);
+    }
 
-impl fmt::Display for Cell {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match &self.state {
-                CellState::Covered => '-',
-                CellState::Exposed => {
-                    if self.has_mine {
-                        '%'
-                    } else {
-                        self.neighboring_mines
-                            .to_string()
-                            .chars()
-                            .collect::<Vec<char>>()[0]
+    fn place_random_mines(&mut self, count: usize, exclude: &[(i32, i32)], rng: &mut impl Rng) {
+        let excluded = exclude
+            .iter()
+            .filter_map(|&(x, y)| self.coord_to_index(x, y))
+            .collect::<Vec<usize>>();
+        let mut candidates = self
+            .cells
+            .iter()
+            .enumerate()
+            .filter(|(index, cell)| !cell.has_mine && !excluded.contains(index))
+            .map(|(_, cell)| cell)
+            .map(|cell| (cell.x, cell.y))
+            .collect::<Vec<(i32, i32)>>();
+        assert!(
+            count <= candidates.len(),
+            "cannot place {} mines in {} free cells",
+            count,
+            candidates.len()
+        );
+
+        let (chosen, _) = candidates.partial_shuffle(rng, count);
+        for (x, y) in chosen.iter() {
+            self.place_mine(*x, *y);
+        }
+    }
+
+    /** Moves the mine at the given coord to the first mine-free cell in
+    row-major order. Returns `false` if there was no mine to move or nowhere
+    to put it */
+    pub fn relocate_mine(&mut self, from_x: i32, from_y: i32) -> bool {
+        let index = self.coord_to_index(from_x, from_y).unwrap();
+        if !self.cells[index].has_mine {
+            return false;
+        }
+        let target = match (0..self.cells.len()).find(|&i| i != index && !self.cells[i].has_mine) {
+            Some(target) => target,
+            None => return false,
+        };
+
+        let (to_x, to_y) = self.index_to_coord(target).unwrap();
+        self.remove_mine(from_x, from_y);
+        self.place_mine(to_x, to_y);
+        true
+    }
+
+    /** Moves any mines on or next to `(x, y)` to random mine-free cells
+    outside that neighborhood, so uncovering it opens a region. Returns
+    `false` if there wasn't room for all of them */
+    pub fn clear_neighborhood(&mut self, x: i32, y: i32, rng: &mut impl Rng) -> bool {
+        let neighborhood = self
+            .neighbor_coords(x, y)
+            .into_iter()
+            .chain(Some((x, y)))
+            .filter_map(|(x, y)| self.coord_to_index(x, y))
+            .collect::<Vec<usize>>();
+        let mines = neighborhood
+            .iter()
+            .copied()
+            .filter(|&index| self.cells[index].has_mine)
+            .collect::<Vec<usize>>();
+        if mines.is_empty() {
+            return true;
+        }
+        let mut free = (0..self.cells.len())
+            .filter(|index| !self.cells[*index].has_mine && !neighborhood.contains(index))
+            .collect::<Vec<usize>>();
+        let moved = mines.len().min(free.len());
+
+        let (chosen, _) = free.partial_shuffle(rng, moved);
+        for (&from, &to) in mines.iter().zip(chosen.iter()) {
+            let (from_x, from_y) = self.index_to_coord(from).unwrap();
+            let (to_x, to_y) = self.index_to_coord(to).unwrap();
+            self.remove_mine(from_x, from_y);
+            self.place_mine(to_x, to_y);
+        }
+        moved == mines.len()
+    }
+
+    /** The board's 3BV (Bechtel's Board Benchmark Value), the fewest clicks
+    that can clear it: one per opening (a connected region of zero cells plus
+    its numbered border) and one per numbered cell outside every opening */
+    pub fn board_3bv(&self) -> u32 {
+        let mut visited = vec![false; self.cells.len()];
+        let mut clicks = 0;
+
+        for index in 0..self.cells.len() {
+            let cell = &self.cells[index];
+            if visited[index] || cell.has_mine || cell.neighboring_mines != 0 {
+                continue;
+            }
+            clicks += 1;
+            visited[index] = true;
+            let mut queue = VecDeque::new();
+            queue.push_back((cell.x, cell.y));
+            while let Some((x, y)) = queue.pop_front() {
+                for (i, j) in self.neighbor_offsets(y).iter() {
+                    if let Some(neighbor_index) = self.coord_to_index(x + i, y + j) {
+                        if !visited[neighbor_index] && !self.cells[neighbor_index].has_mine {
+                            visited[neighbor_index] = true;
+                            if self.cells[neighbor_index].neighboring_mines == 0 {
+                                queue.push_back((x + i, y + j));
+                            }
+                        }
                     }
                 }
-                CellState::Flagged => 'F',
             }
-        )
+        }
+
+        clicks
+            + (0..self.cells.len())
+                .filter(|&index| !visited[index] && !self.cells[index].has_mine)
+                .count() as u32
+    }
+
+    /** The fewest clicks that clear the board. This is its 3BV, the name
+    speedrunners know it by, so `board_3bv` is the one to reach for */
+    pub fn min_clicks(&self) -> u32 {
+        self.board_3bv()
+    }
+
+    /** A rough score for sorting boards from easy to hard, adding up:
+    - `10 * mines / cells`, how crowded the mines are
+    - `5 * 3BV / cells`, how many clicks each cell costs
+    - `2` if the solver can't clear the board from its first opening (or
+      first safe cell when it has none) without guessing
+
+    Beginner boards score about 2 to 5 and expert ones about 6 */
+    pub fn difficulty_estimate(&self) -> f32 {
+        let cells = self.cells.len() as f32;
+        if cells == 0. || self.mine_count == 0 {
+            return 0.;
+        }
+        let density = self.density();
+        let clicks = self.board_3bv() as f32 / cells;
+        let start = self
+            .cells
+            .iter()
+            .find(|cell| !cell.has_mine && cell.neighboring_mines == 0)
+            .or_else(|| self.cells.iter().find(|cell| !cell.has_mine));
+        let guessing = match start {
+            Some(cell) if self.solvable_from(cell.x, cell.y) => 0.,
+            _ => 2.,
+        };
+        10. * density + 5. * clicks + guessing
+    }
+
+    /** Checks every stored neighbor count against the mines around it,
+    returning the coords of any that disagree */
+    pub fn validate(&self) -> Result<(), Vec<(i32, i32)>> {
+        let mismatched = self
+            .cells
+            .iter()
+            .filter(|cell| {
+                let mines = self
+                    .get_neighbors(cell.x, cell.y)
+                    .iter()
+                    .filter(|neighbor| neighbor.has_mine)
+                    .count();
+                mines != cell.neighboring_mines as usize
+            })
+            .map(|cell| (cell.x, cell.y))
+            .collect::<Vec<(i32, i32)>>();
+        if mismatched.is_empty() {
+            Ok(())
+        } else {
+            Err(mismatched)
+        }
+    }
+
+    /** Safe cells the player still has to uncover */
+    pub fn remaining_safe_cells(&self) -> i32 {
+        self.cells.len() as i32 - self.mine_count - self.exposed_safe_cells
+    }
+
+    pub fn mine_count(&self) -> i32 {
+        self.mine_count
+    }
+
+    /** The fraction of cells holding a mine, or 0 for a board with no cells */
+    pub fn density(&self) -> f32 {
+        if self.cells.is_empty() {
+            return 0.;
+        }
+        self.mine_count as f32 / self.cells.len() as f32
+    }
+
+    /** Flags on the board, including ones already marked wrong after a
+    loss */
+    pub fn count_flags(&self) -> i32 {
+        self.cells
+            .iter()
+            .filter(|cell| matches!(cell.state, CellState::Flagged | CellState::WronglyFlagged))
+            .count() as i32
+    }
+
+    /** Flags placed on cells without a mine, including ones already marked
+    wrong after a loss */
+    pub fn wrong_flag_count(&self) -> u32 {
+        self.cells
+            .iter()
+            .filter(|cell| match cell.state {
+                CellState::Flagged => !cell.has_mine,
+                CellState::WronglyFlagged => true,
+                _ => false,
+            })
+            .count() as u32
+    }
+
+    /** Coords of every mine in row-major order. This is the answer key, so
+    nothing that plays the board (like the solver) should look at it */
+    pub fn mine_positions(&self) -> Vec<(i32, i32)> {
+        self.cells
+            .iter()
+            .filter(|cell| cell.has_mine)
+            .map(|cell| (cell.x, cell.y))
+            .collect()
+    }
+
+    /** Whether every safe cell is exposed, which is also when uncovering
+    reports the board `Cleared`. Loading a board counts its exposed cells, so
+    this also holds for boards saved already cleared */
+    pub fn is_won(&self) -> bool {
+        self.remaining_safe_cells() == 0
+    }
+
+    /** Whether every mine is flagged and no safe cell is */
+    pub fn all_mines_flagged(&self) -> bool {
+        self.mine_count > 0
+            && self
+                .cells
+                .iter()
+                .all(|cell| cell.has_mine == cell.is_flagged())
+    }
+
+    /** Encodes the board as a `width height` line followed by one line per
+    row. Each cell is a state letter (`C`overed, `E`xposed, `F`lagged,
+    `Q`uestioned, `D`etonated, `W`rongly flagged), `*` or `.` for a mine, and
+    its neighboring mine count */
+    pub fn serialize(&self) -> String {
+        let mut out = format!("{} {}", self.width, self.height);
+        if self.wrap {
+            out.push_str(" wrap");
+        }
+        if self.topology == Topology::Hex {
+            out.push_str(" hex");
+        }
+        if self.neighbor_mode == NeighborMode::Four {
+            out.push_str(" orthogonal");
+        }
+        out.push('\n');
+        for row in self.cells.chunks(self.width.max(1) as usize) {
+            let tokens = row
+                .iter()
+                .map(|cell| {
+                    let state = match cell.state {
+                        CellState::Covered => 'C',
+                        CellState::Exposed => 'E',
+                        CellState::Flagged => 'F',
+                        CellState::Questioned => 'Q',
+                        CellState::Detonated => 'D',
+                        CellState::WronglyFlagged => 'W',
+                    };
+                    let mine = if cell.has_mine { '*' } else { '.' };
+                    format!("{}{}{}", state, mine, cell.neighboring_mines)
+                })
+                .collect::<Vec<String>>();
+            out.push_str(&tokens.join(" "));
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn deserialize(s: &str) -> Result<Grid, ParseError> {
+        let mut lines = s.lines();
+        let header = lines
+            .next()
+            .ok_or_else(|| ParseError::new(1, "missing dimensions"))?;
+        let mut header = header.split_whitespace().collect::<Vec<&str>>();
+        let options = header.split_off(header.len().min(2));
+        let dimensions = header
+            .iter()
+            .map(|n| n.parse::<i32>())
+            .collect::<Result<Vec<i32>, _>>()
+            .map_err(|_| ParseError::new(1, "dimensions must be integers"))?;
+        let (width, height) = match dimensions[..] {
+            [width, height] if width > 0 && height > 0 => (width, height),
+            _ => return Err(ParseError::new(1, "expected a positive width and height")),
+        };
+        if width > MAX_SIDE || height > MAX_SIDE {
+            return Err(ParseError::new(
+                1,
+                &format!("neither side can be over {}", MAX_SIDE),
+            ));
+        }
+        // Check every row is there before allocating for them
+        let rows = lines.by_ref().take(height as usize).collect::<Vec<&str>>();
+        if rows.len() < height as usize {
+            return Err(ParseError::new(rows.len() + 2, "missing row"));
+        }
+
+        let mut grid = Grid::new(width, height);
+        for option in options {
+            match option {
+                "wrap" => grid.wrap = true,
+                "hex" => grid.topology = Topology::Hex,
+                "orthogonal" => grid.neighbor_mode = NeighborMode::Four,
+                _ => return Err(ParseError::new(1, &format!("unknown option '{}'", option))),
+            }
+        }
+        for (y, line) in rows.iter().enumerate() {
+            let y = y as i32;
+            let line_number = y as usize + 2;
+            let tokens = line.split_whitespace().collect::<Vec<&str>>();
+            if tokens.len() != width as usize {
+                return Err(ParseError::new(
+                    line_number,
+                    &format!("expected {} cells, found {}", width, tokens.len()),
+                ));
+            }
+            for (x, token) in tokens.iter().enumerate() {
+                let invalid = || ParseError::new(line_number, &format!("invalid cell '{}'", token));
+                let mut chars = token.chars();
+                let state = match chars.next() {
+                    Some('C') => CellState::Covered,
+                    Some('E') => CellState::Exposed,
+                    Some('F') => CellState::Flagged,
+                    Some('Q') => CellState::Questioned,
+                    Some('D') => CellState::Detonated,
+                    Some('W') => CellState::WronglyFlagged,
+                    _ => return Err(invalid()),
+                };
+                let has_mine = match chars.next() {
+                    Some('*') => true,
+                    Some('.') => false,
+                    _ => return Err(invalid()),
+                };
+                let neighboring_mines = chars.as_str().parse::<u8>().map_err(|_| invalid())?;
+
+                let index = x + (y * width) as usize;
+                grid.cells[index].state = state;
+                grid.cells[index].has_mine = has_mine;
+                grid.cells[index].neighboring_mines = neighboring_mines;
+                if has_mine {
+                    grid.mine_count += 1;
+                } else if state == CellState::Exposed {
+                    grid.exposed_safe_cells += 1;
+                }
+            }
+        }
+        if let Err(mismatched) = grid.validate() {
+            let (x, y) = mismatched[0];
+            return Err(ParseError::new(
+                y as usize + 2,
+                &format!("mine count at ({}, {}) doesn't match its neighbors", x, y),
+            ));
+        }
+        Ok(grid)
+    }
+
+    /** Reads a mine layout drawn one row per line, with `*` for a mine and
+    `.` for a safe cell. Blank lines are skipped and every row must be the
+    same width */
+    pub fn from_ascii(s: &str) -> Result<Grid, ParseError> {
+        let rows = s
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .collect::<Vec<(usize, &str)>>();
+        let width = match rows.first() {
+            Some((_, row)) => row.chars().count(),
+            None => return Err(ParseError::new(1, "no rows")),
+        };
+
+        let mut mines = vec![];
+        for (y, (line_number, row)) in rows.iter().enumerate() {
+            if row.chars().count() != width {
+                return Err(ParseError::new(
+                    *line_number,
+                    &format!("expected {} cells, found {}", width, row.chars().count()),
+                ));
+            }
+            for (x, c) in row.chars().enumerate() {
+                match c {
+                    '*' => mines.push((x as i32, y as i32)),
+                    '.' => {}
+                    _ => {
+                        return Err(ParseError::new(
+                            *line_number,
+                            &format!("invalid cell '{}'", c),
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(Grid::with_mines(width as i32, rows.len() as i32, &mines))
+    }
+
+    /** Packs the mine layout into bytes: width and height as little-endian
+    `u16`s, a byte of options (bit 0 wrap, bit 1 hex, bit 2 four neighbors),
+    then one bit per cell in row-major order. Marks and uncovered cells
+    aren't kept */
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&(self.width as u16).to_le_bytes());
+        bytes.extend_from_slice(&(self.height as u16).to_le_bytes());
+        bytes.push(
+            self.wrap as u8
+                | ((self.topology == Topology::Hex) as u8) << 1
+                | ((self.neighbor_mode == NeighborMode::Four) as u8) << 2,
+        );
+        let mut mines = vec![0u8; self.cells.len().div_ceil(8)];
+        for (index, cell) in self.cells.iter().enumerate() {
+            if cell.has_mine {
+                mines[index / 8] |= 1 << (index % 8);
+            }
+        }
+        bytes.extend(mines);
+        bytes
+    }
+
+    /** Unpacks a board written by `to_bytes`, with every cell covered. The
+    bytes must be exactly as long as the header says */
+    pub fn from_bytes(bytes: &[u8]) -> Result<Grid, GridError> {
+        if bytes.len() < 5 {
+            return Err(GridError::Truncated {
+                expected: 5,
+                found: bytes.len(),
+            });
+        }
+        let width = u16::from_le_bytes([bytes[0], bytes[1]]) as i32;
+        let height = u16::from_le_bytes([bytes[2], bytes[3]]) as i32;
+        if width == 0 || height == 0 {
+            return Err(GridError::InvalidDimensions { width, height });
+        }
+        if width > MAX_SIDE || height > MAX_SIDE {
+            return Err(GridError::TooLarge { width, height });
+        }
+        let cell_count = (width as usize)
+            .checked_mul(height as usize)
+            .ok_or(GridError::TooLarge { width, height })?;
+        let expected = 5 + cell_count.div_ceil(8);
+        if bytes.len() != expected {
+            return Err(GridError::Truncated {
+                expected,
+                found: bytes.len(),
+            });
+        }
+
+        let mut grid = Grid::new(width, height);
+        grid.wrap = bytes[4] & 1 != 0;
+        if bytes[4] & 2 != 0 {
+            grid.topology = Topology::Hex;
+        }
+        if bytes[4] & 4 != 0 {
+            grid.neighbor_mode = NeighborMode::Four;
+        }
+        let mines = &bytes[5..];
+        for index in 0..cell_count {
+            if mines[index / 8] & (1 << (index % 8)) != 0 {
+                grid.cells[index].has_mine = true;
+                grid.mine_count += 1;
+            }
+        }
+        grid.recompute_counts();
+        Ok(grid)
+    }
+
+    /** `to_bytes` as base64, for sharing a puzzle as text */
+    pub fn to_code(&self) -> String {
+        base64::encode(&self.to_bytes())
+    }
+
+    pub fn from_code(code: &str) -> Result<Grid, GridError> {
+        Grid::from_bytes(&base64::decode(code).ok_or(GridError::InvalidCode)?)
+    }
+
+    /** What the player can see, using the same glyphs as `Cell`'s `Display`.
+    Covered cells show as `-` whatever is under them */
+    pub fn render_player_view(&self) -> String {
+        self.render(|cell| cell.to_string())
+    }
+
+    /** One line per row with cells separated by spaces */
+    fn render(&self, glyph: impl Fn(&Cell) -> String) -> String {
+        let mut out = String::new();
+        for (x, y, cell) in self.iter_cells() {
+            if x > 0 {
+                out.push(' ');
+            } else if y > 0 {
+                out.push('\n');
+            }
+            out.push_str(&glyph(cell));
+        }
+        out
+    }
+
+    /** Returns `None` if coord is out of bounds. Wrapping boards fold every
+    coord back onto the board instead, except that hex boards with an odd
+    number of rows only wrap sideways: folding rows would join two rows
+    shifted the same way, and their cells wouldn't neighbor each other both
+    ways round */
+    pub fn coord_to_index(&self, x: i32, y: i32) -> Option<usize> {
+        if self.wrap && self.width > 0 && self.height > 0 {
+            let x = x.rem_euclid(self.width);
+            let y = if self.topology != Topology::Hex || self.height % 2 == 0 {
+                y.rem_euclid(self.height)
+            } else {
+                y
+            };
+            if y < 0 || y >= self.height {
+                return None;
+            }
+            return Some((x + y * self.width) as usize);
+        }
+        if x >= 0 && x < self.width && y >= 0 && y < self.height {
+            Some((x + y * self.width) as usize)
+        } else {
+            None
+        }
+    }
+
+    /** Returns `None` if index is out of bounds */
+    pub fn index_to_coord(&self, index: usize) -> Option<(i32, i32)> {
+        if index < self.cells.len() {
+            let index = index as i32;
+            Some((index % self.width, index / self.width))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(line: usize, message: &str) -> Self {
+        ParseError {
+            line,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Debug, PartialEq)]
+pub enum GridError {
+    TooManyMines { requested: i32, capacity: i32 },
+    InvalidDimensions { width: i32, height: i32 },
+    TooLarge { width: i32, height: i32 },
+    Truncated { expected: usize, found: usize },
+    InvalidCode,
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GridError::TooManyMines {
+                requested,
+                capacity,
+            } => write!(
+                f,
+                "{} mines requested but the board only has room for {}",
+                requested, capacity
+            ),
+            GridError::InvalidDimensions { width, height } => {
+                write!(f, "a {}x{} board has no cells", width, height)
+            }
+            GridError::TooLarge { width, height } => write!(
+                f,
+                "a {}x{} board is too big, neither side can be over {}",
+                width, height, MAX_SIDE
+            ),
+            GridError::Truncated { expected, found } => {
+                write!(f, "expected {} bytes but found {}", expected, found)
+            }
+            GridError::InvalidCode => write!(f, "not a valid puzzle code"),
+        }
+    }
+}
+
+impl Error for GridError {}
+
+/** The full solution, ignoring what the player has uncovered: `%` for
+mines and the neighboring mine count everywhere else */
+impl fmt::Display for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.render(|cell| {
+                if cell.has_mine {
+                    '%'.to_string()
+                } else {
+                    cell.neighboring_mines.to_string()
+                }
+            })
+        )
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Cell {
+    state: CellState,
+    pub has_mine: bool,
+    neighboring_mines: u8,
+    x: i32,
+    y: i32,
+    /** How far into the game the player uncovered this cell */
+    #[cfg_attr(feature = "serde", serde(default))]
+    revealed_at: Option<Duration>,
+}
+
+impl Cell {
+    fn new(state: CellState, has_mine: bool, neighboring_mines: u8, x: i32, y: i32) -> Self {
+        Cell {
+            state,
+            has_mine,
+            neighboring_mines,
+            x,
+            y,
+            revealed_at: None,
+        }
+    }
+
+    pub fn state(&self) -> &CellState {
+        &self.state
+    }
+
+    pub fn neighboring_mines(&self) -> u8 {
+        self.neighboring_mines
+    }
+
+    pub fn coords(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
+
+    pub fn revealed_at(&self) -> Option<Duration> {
+        self.revealed_at
+    }
+
+    pub fn is_exposed(&self) -> bool {
+        self.state == CellState::Exposed
+    }
+
+    /** Whether the cell shows what's under it: exposed, or the mine that
+    went off */
+    pub fn is_revealed(&self) -> bool {
+        matches!(self.state, CellState::Exposed | CellState::Detonated)
+    }
+
+    /** Whether the cell holds a flag the game hasn't marked wrong */
+    pub fn is_flagged(&self) -> bool {
+        self.state == CellState::Flagged
+    }
+
+    /** Whether the cell is covered with no mark on it */
+    pub fn is_covered(&self) -> bool {
+        self.state == CellState::Covered
+    }
+
+    pub fn is_questioned(&self) -> bool {
+        self.state == CellState::Questioned
+    }
+
+    pub fn sprite_index(&self, theme: Theme) -> usize {
+        match self.state {
+            CellState::Covered => theme.covered(),
+            CellState::Exposed => {
+                if self.has_mine {
+                    theme.mine()
+                } else {
+                    if self.neighboring_mines == 0 {
+                        theme.empty()
+                    } else {
+                        self.neighboring_mines as usize
+                    }
+                }
+            }
+            CellState::Flagged => 11,
+            CellState::Questioned => 12,
+            // Drawn with a red tint and a cross respectively
+            CellState::Detonated => theme.mine(),
+            CellState::WronglyFlagged => theme.mine(),
+        }
+    }
+}
+
+/** Which sprites cells are drawn with */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Theme {
+    #[default]
+    Classic,
+    /** Covered cells are drawn plain and empty ones hatched, so the part of
+    the board still to clear stands out less than what's been cleared.
+    Mines are drawn solid */
+    Inverted,
+}
+
+impl Theme {
+    pub fn cycled(&self) -> Theme {
+        match self {
+            Theme::Classic => Theme::Inverted,
+            Theme::Inverted => Theme::Classic,
+        }
+    }
+
+    /** Sprite for a covered cell with no mark on it */
+    pub fn covered(&self) -> usize {
+        match self {
+            Theme::Classic => 13,
+            Theme::Inverted => 14,
+        }
+    }
+
+    /** Sprite for an exposed cell with no neighboring mines */
+    pub fn empty(&self) -> usize {
+        match self {
+            Theme::Classic => 14,
+            Theme::Inverted => 13,
+        }
+    }
+
+    /** Sprite for a mine */
+    pub fn mine(&self) -> usize {
+        match self {
+            Theme::Classic => 10,
+            Theme::Inverted => 16,
+        }
+    }
+}
+
+/** A read-only view of a cell that hides anything the player can't see */
+#[derive(Clone, Debug, PartialEq)]
+pub struct CellView {
+    pub x: i32,
+    pub y: i32,
+    pub state: CellState,
+    /** `None` unless the cell is exposed */
+    pub neighboring_mines: Option<u8>,
+    /** `None` unless the cell is exposed */
+    pub has_mine: Option<bool>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CellState {
+    Covered,
+    Exposed,
+    Flagged,
+    Questioned,
+    /** The mine that ended the game */
+    Detonated,
+    /** A flag on a safe cell, revealed once the game is lost */
+    WronglyFlagged,
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match &self.state {
+                CellState::Covered => '-',
+                CellState::Exposed => {
+                    if self.has_mine {
+                        '%'
+                    } else {
+                        self.neighboring_mines
+                            .to_string()
+                            .chars()
+                            .collect::<Vec<char>>()[0]
+                    }
+                }
+                CellState::Flagged => 'F',
+                CellState::Questioned => '?',
+                CellState::Detonated => '!',
+                CellState::WronglyFlagged => 'X',
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn density_estimates_never_prove_a_cell_safe() {
+        let mut grid = Grid::with_mines(11, 1, &[(2, 0), (5, 0), (8, 0)]);
+        for x in [1, 3, 7, 9] {
+            grid.uncover(x, 0);
+        }
+        // Every mine but the middle one is expected on the frontier, which
+        // leaves the middle cell a density of zero
+        assert_eq!(solver::probabilities(&grid)[&(5, 0)], 0.);
+        assert!(!grid.is_provably_safe(5, 0));
+    }
+
+    #[test]
+    fn puzzle_codes_round_trip() {
+        let mut grid = Grid::with_mines(7, 5, &[(0, 0), (3, 2), (6, 4), (2, 4)]);
+        grid.set_topology(Topology::Hex);
+        let decoded = Grid::from_code(&grid.to_code()).unwrap();
+        assert_eq!((decoded.width, decoded.height), (7, 5));
+        assert_eq!(decoded.topology(), Topology::Hex);
+        assert_eq!(decoded.mine_positions(), grid.mine_positions());
+        assert_eq!(decoded.to_string(), grid.to_string());
+    }
+
+    #[test]
+    fn malformed_puzzle_codes_are_rejected() {
+        // 50000x50000 would overflow the cell count and allocate gigabytes
+        let mut huge = vec![];
+        huge.extend_from_slice(&50000u16.to_le_bytes());
+        huge.extend_from_slice(&50000u16.to_le_bytes());
+        huge.push(0);
+        assert_eq!(
+            Grid::from_bytes(&huge).err(),
+            Some(GridError::TooLarge {
+                width: 50000,
+                height: 50000
+            })
+        );
+        assert_eq!(
+            Grid::from_code(&base64::encode(&huge)).err(),
+            Some(GridError::TooLarge {
+                width: 50000,
+                height: 50000
+            })
+        );
+
+        let mut bytes = Grid::with_mines(4, 4, &[(1, 1)]).to_bytes();
+        assert!(Grid::from_bytes(&bytes[..3]).is_err());
+        assert_eq!(
+            Grid::from_bytes(&bytes[..bytes.len() - 1]).err(),
+            Some(GridError::Truncated {
+                expected: 7,
+                found: 6
+            })
+        );
+        bytes.push(0);
+        assert!(Grid::from_bytes(&bytes).is_err());
+        assert_eq!(
+            Grid::from_code("not a code!").err(),
+            Some(GridError::InvalidCode)
+        );
+    }
+
+    #[test]
+    fn saves_round_trip() {
+        let mut grid = Grid::with_mines(5, 4, &[(0, 0), (4, 3)]);
+        grid.uncover(2, 2);
+        grid.cycle_mark(0, 0);
+        let loaded = Grid::deserialize(&grid.serialize()).unwrap();
+        assert_eq!(loaded.serialize(), grid.serialize());
+        assert_eq!(loaded.remaining_safe_cells(), grid.remaining_safe_cells());
+    }
+
+    #[test]
+    fn malformed_saves_are_rejected() {
+        let oversized = Grid::deserialize("70000 70000\n").err().unwrap();
+        assert_eq!(oversized.line, 1);
+
+        let save = Grid::with_mines(3, 3, &[(1, 1)]).serialize();
+        let truncated = save.lines().take(3).collect::<Vec<&str>>().join("\n");
+        assert_eq!(
+            Grid::deserialize(&truncated).err(),
+            Some(ParseError::new(4, "missing row"))
+        );
+        assert!(Grid::deserialize("").is_err());
+        assert!(Grid::deserialize("3 x\n").is_err());
+        assert!(Grid::deserialize(&save.replace("C.1", "C.7")).is_err());
+    }
+
+    #[test]
+    fn wrapped_neighbors_are_symmetric() {
+        for (width, height) in [(9, 9), (9, 8), (8, 9), (4, 5), (3, 3)] {
+            let shapes = [
+                (Topology::Square, NeighborMode::Eight),
+                (Topology::Square, NeighborMode::Four),
+                (Topology::Hex, NeighborMode::Eight),
+                (Topology::Hex, NeighborMode::Four),
+            ];
+            for &(topology, neighbor_mode) in shapes.iter() {
+                let mut grid = Grid::new_wrapping(width, height);
+                grid.set_topology(topology);
+                grid.set_neighbor_mode(neighbor_mode);
+                for (x, y, _) in grid.iter_cells() {
+                    for (i, j) in grid.neighbor_coords(x, y) {
+                        assert!(
+                            grid.neighbor_coords(i, j).contains(&(x, y)),
+                            "{:?} {:?} {}x{}: ({}, {}) neighbors ({}, {}) but not back",
+                            topology,
+                            neighbor_mode,
+                            width,
+                            height,
+                            x,
+                            y,
+                            i,
+                            j
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut grid = Grid::new_wrapping(9, 9);
+        grid.set_topology(Topology::Hex);
+        assert!(!grid.neighbor_coords(4, 8).contains(&(4, 0)));
+        grid.place_mine(4, 8);
+        assert_eq!(grid.validate(), Ok(()));
+        assert!(Grid::deserialize(&grid.serialize()).is_ok());
+    }
+
+    #[test]
+    fn themes_change_the_mine_sprite() {
+        let mut grid = Grid::with_mines(2, 1, &[(0, 0)]);
+        grid.uncover_bombs();
+        let mine = grid.get(0, 0).unwrap();
+        assert_ne!(
+            mine.sprite_index(Theme::Classic),
+            mine.sprite_index(Theme::Classic.cycled())
+        );
+        assert_eq!(mine.sprite_index(Theme::Inverted), Theme::Inverted.mine());
+    }
+
+    #[test]
+    fn boards_without_mines_are_won_once_uncovered() {
+        let mut grid = Grid::new(4, 3);
+        assert!(!grid.is_won());
+        assert_eq!(grid.uncover(0, 0), BoardState::Cleared);
+        assert!(grid.is_won());
+    }
+
+    /** Safe cells not yet exposed, counted the slow way */
+    fn scanned_remaining(grid: &Grid) -> i32 {
+        grid.iter_cells()
+            .filter(|(_, _, cell)| !cell.has_mine && !cell.is_exposed())
+            .count() as i32
+    }
+
+    #[test]
+    fn exposed_counter_matches_a_full_scan() {
+        let mut grid = Grid::with_mines(6, 5, &[(0, 0), (5, 0), (2, 3), (5, 4)]);
+        assert_eq!(grid.remaining_safe_cells(), scanned_remaining(&grid));
+        grid.uncover(1, 0);
+        assert_eq!(grid.remaining_safe_cells(), scanned_remaining(&grid));
+        grid.cycle_mark(0, 0);
+        grid.chord(1, 0);
+        assert_eq!(grid.remaining_safe_cells(), scanned_remaining(&grid));
+        grid.restore_mark(0, 0, CellState::Covered);
+        assert_eq!(grid.remaining_safe_cells(), scanned_remaining(&grid));
+
+        let mut grid = Grid::deserialize(&grid.serialize()).unwrap();
+        assert_eq!(grid.remaining_safe_cells(), scanned_remaining(&grid));
+        grid.remove_mine(5, 4);
+        grid.uncover(5, 4);
+        assert_eq!(grid.remaining_safe_cells(), scanned_remaining(&grid));
+        grid.place_mine(5, 4);
+        assert_eq!(grid.remaining_safe_cells(), scanned_remaining(&grid));
+
+        for (x, y) in grid.snapshot().iter().map(|cell| (cell.x, cell.y)) {
+            if !grid.get(x, y).unwrap().has_mine {
+                grid.uncover(x, y);
+            }
+        }
+        assert_eq!(grid.remaining_safe_cells(), 0);
+        assert_eq!(scanned_remaining(&grid), 0);
+        assert!(grid.is_won());
+    }
+
+    #[test]
+    fn no_guess_boards_are_solvable_from_the_start() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for (width, height, mines) in [(9, 9, 10), (16, 16, 40), (30, 16, 99)] {
+            let start = (width / 2, height / 2);
+            let mut grid = Grid::new(width, height);
+            assert!(grid.populate_no_guess(mines, start, &mut rng));
+            assert_eq!(grid.mine_count(), mines);
+            assert!(grid.solvable_from(start.0, start.1));
+            assert_eq!(grid.get(start.0, start.1).unwrap().neighboring_mines(), 0);
+        }
+    }
+
+    #[test]
+    fn four_neighbor_counts_skip_diagonals() {
+        let mut grid = Grid::with_mines(3, 3, &[(0, 0)]);
+        assert_eq!(grid.get(1, 1).unwrap().neighboring_mines(), 1);
+        assert_eq!(grid.get(1, 0).unwrap().neighboring_mines(), 1);
+
+        grid.set_neighbor_mode(NeighborMode::Four);
+        assert_eq!(grid.get(1, 1).unwrap().neighboring_mines(), 0);
+        assert_eq!(grid.get(1, 0).unwrap().neighboring_mines(), 1);
+        assert_eq!(grid.get(0, 1).unwrap().neighboring_mines(), 1);
+
+        // Hex boards keep their shape with four neighbors too
+        grid.set_topology(Topology::Hex);
+        let code = grid.to_code();
+        let loaded = Grid::from_code(&code).unwrap();
+        assert_eq!(loaded.topology(), Topology::Hex);
+        assert_eq!(loaded.neighbor_mode(), NeighborMode::Four);
+        let loaded = Grid::deserialize(&grid.serialize()).unwrap();
+        assert_eq!(loaded.topology(), Topology::Hex);
+        assert_eq!(loaded.neighbor_mode(), NeighborMode::Four);
+    }
+
+    #[test]
+    fn min_clicks_counts_openings_and_lone_numbers() {
+        // One opening reaches every number around the corner mine
+        assert_eq!(Grid::with_mines(3, 3, &[(0, 0)]).min_clicks(), 1);
+        // No zero cells, so each number is a click of its own
+        assert_eq!(Grid::with_mines(3, 1, &[(1, 0)]).min_clicks(), 2);
+        // An opening on the left, and a number it doesn't reach on the right
+        let grid = Grid::with_mines(5, 1, &[(3, 0)]);
+        assert_eq!(grid.min_clicks(), 2);
+        assert_eq!(grid.min_clicks(), grid.board_3bv());
+    }
+
+    #[test]
+    fn uncovering_every_safe_cell_clears_the_board() {
+        // No zero cells, so each safe cell takes an uncover of its own
+        let mut grid = Grid::with_mines(3, 2, &[(1, 0), (1, 1)]);
+        for &(x, y) in [(0, 0), (0, 1), (2, 0)].iter() {
+            assert_eq!(grid.uncover(x, y), BoardState::InProgress);
+        }
+        assert_eq!(grid.uncover(2, 1), BoardState::Cleared);
+        // Uncovering again doesn't count the cell twice
+        assert_eq!(grid.uncover(2, 1), BoardState::Cleared);
+        assert_eq!(grid.remaining_safe_cells(), 0);
+    }
+
+    #[test]
+    fn repeated_mines_are_placed_once() {
+        let mut grid = Grid::new(3, 3);
+        assert!(grid.place_mine(1, 1));
+        assert!(!grid.place_mine(1, 1));
+        assert_eq!(grid.mine_count(), 1);
+        assert_eq!(grid.get(0, 0).unwrap().neighboring_mines(), 1);
+
+        let mut grid = Grid::new(10, 10);
+        grid.seed_mines(99, &mut StdRng::seed_from_u64(1));
+        assert_eq!(grid.mine_count(), 99);
+        assert_eq!(grid.mine_positions().len(), 99);
+    }
+
+    /** Every cell's neighboring mine count, in row-major order */
+    fn counts(grid: &Grid) -> Vec<u8> {
+        grid.iter_cells()
+            .map(|(_, _, cell)| cell.neighboring_mines())
+            .collect()
+    }
+
+    #[test]
+    fn relocating_a_mine_keeps_counts_consistent() {
+        let mut grid = Grid::with_mines(3, 3, &[(0, 0), (1, 0)]);
+        assert!(grid.relocate_mine(0, 0));
+        assert_eq!(grid.mine_positions(), vec![(1, 0), (2, 0)]);
+        let mut recounted = grid.clone();
+        recounted.recompute_counts();
+        assert_eq!(counts(&grid), counts(&recounted));
+        assert_eq!(grid.get(0, 1).unwrap().neighboring_mines(), 1);
+        assert!(!grid.relocate_mine(0, 0));
+
+        for seed in 0..20 {
+            let mut grid = Grid::new(4, 4);
+            grid.populate_around(
+                15,
+                (seed % 4, seed / 4 % 4),
+                &mut StdRng::seed_from_u64(seed as u64),
+            );
+            assert!(!grid.get(seed % 4, seed / 4 % 4).unwrap().has_mine);
+        }
+    }
+
+    #[test]
+    fn the_top_left_corner_has_neighbors() {
+        let mut grid = Grid::new(3, 3);
+        let mut neighbors = grid.neighbor_coords(0, 0);
+        neighbors.sort();
+        assert_eq!(neighbors, vec![(0, 1), (1, 0), (1, 1)]);
+        grid.place_mine(1, 1);
+        assert_eq!(grid.get(0, 0).unwrap().neighboring_mines(), 1);
+    }
+
+    #[test]
+    fn recounts_cover_corners_edges_and_the_center() {
+        // * * .
+        // * . .
+        // . . *
+        let mut grid = Grid::with_mines(3, 3, &[(0, 0), (1, 0), (0, 1), (2, 2)]);
+        for (_, _, cell) in grid.iter_mut_cells() {
+            cell.neighboring_mines = 0;
+        }
+        grid.recompute_counts();
+        assert_eq!(counts(&grid), vec![2, 2, 1, 2, 4, 2, 1, 2, 0]);
+    }
+
+    #[test]
+    fn chords_open_satisfied_numbers() {
+        // . * .
+        // . . .
+        let mut grid = Grid::with_mines(3, 2, &[(1, 0)]);
+        grid.uncover(1, 1);
+        assert_eq!(grid.chord(1, 1), BoardState::InProgress);
+        assert!(grid.get(0, 0).unwrap().is_covered());
+
+        grid.flag(1, 0);
+        assert_eq!(grid.chord(1, 1), BoardState::Cleared);
+
+        // A flag on the wrong cell sets off the real mine
+        let mut grid = Grid::with_mines(3, 2, &[(1, 0)]);
+        grid.uncover(1, 1);
+        grid.flag(0, 0);
+        assert_eq!(grid.chord(1, 1), BoardState::Detonated);
+        assert_eq!(*grid.get(1, 0).unwrap().state(), CellState::Detonated);
+    }
+
+    #[test]
+    fn large_openings_flood_without_recursing() {
+        let mut grid = Grid::new(100, 100);
+        assert_eq!(grid.uncover(50, 50), BoardState::Cleared);
+        assert!(grid.iter_cells().all(|(_, _, cell)| cell.is_exposed()));
+    }
+
+    #[test]
+    fn seeds_lay_the_same_mines() {
+        let layout = |seed| {
+            let mut grid = Grid::new(16, 16);
+            grid.seed_mines(40, &mut StdRng::seed_from_u64(seed));
+            grid.iter_cells()
+                .map(|(_, _, cell)| cell.has_mine)
+                .collect::<Vec<bool>>()
+        };
+        assert_eq!(layout(12), layout(12));
+        assert_ne!(layout(12), layout(13));
+    }
+
+    #[test]
+    fn uncovering_off_the_board_is_ignored() {
+        let mut grid = Grid::with_mines(3, 3, &[(1, 1)]);
+        for &(x, y) in [(-1, -1), (3, 0), (0, 3), (-1, 1)].iter() {
+            assert_eq!(grid.uncover(x, y), BoardState::InProgress);
+        }
+        assert_eq!(grid.remaining_safe_cells(), 8);
+    }
+
+    #[test]
+    fn marking_off_the_board_is_ignored() {
+        let mut grid = Grid::new(3, 3);
+        for &(x, y) in [(-1, 0), (0, -1), (3, 0), (0, 3), (-5, 9)].iter() {
+            assert_eq!(grid.cycle_mark(x, y), 0);
+            assert_eq!(grid.flag(x, y), 0);
+            assert_eq!(grid.restore_mark(x, y, CellState::Flagged), 0);
+            assert!(grid.get(x, y).is_none());
+        }
+        assert_eq!(grid.count_flags(), 0);
+    }
+
+    #[test]
+    fn board_3bv_matches_hand_counts() {
+        // Fully open: one click clears it
+        assert_eq!(Grid::new(4, 4).board_3bv(), 1);
+        // Fully dense: the lone safe cell is a number of its own
+        assert_eq!(
+            Grid::with_mines(2, 2, &[(0, 0), (1, 0), (0, 1)]).board_3bv(),
+            1
+        );
+        // . . . * .
+        // . . . . .
+        // * . . . .
+        // Openings in the top left and bottom right, plus the 1 at (4, 0)
+        // that neither reaches
+        let grid = Grid::with_mines(5, 3, &[(3, 0), (0, 2)]);
+        assert_eq!(grid.board_3bv(), 3);
+    }
+
+    #[test]
+    fn losses_show_right_and_wrong_flags() {
+        let mut grid = Grid::with_mines(4, 1, &[(0, 0), (1, 0), (3, 0)]);
+        grid.flag(0, 0);
+        grid.flag(2, 0);
+        assert_eq!(grid.uncover(3, 0), BoardState::Detonated);
+        let states = grid
+            .iter_cells()
+            .map(|(_, _, cell)| *cell.state())
+            .collect::<Vec<CellState>>();
+        assert_eq!(
+            states,
+            vec![
+                CellState::Flagged,
+                CellState::Exposed,
+                CellState::WronglyFlagged,
+                CellState::Detonated,
+            ]
+        );
+        assert_eq!(grid.wrong_flag_count(), 1);
+    }
+
+    #[test]
+    fn cells_report_their_state_count_and_coords() {
+        let mut grid = Grid::with_mines(3, 2, &[(0, 0), (1, 0)]);
+        grid.uncover(2, 1);
+        let cell = grid.get(2, 1).unwrap();
+        assert_eq!(*cell.state(), CellState::Exposed);
+        assert_eq!(cell.neighboring_mines(), 1);
+        assert_eq!(cell.coords(), (2, 1));
+        let cell = grid.get(0, 1).unwrap();
+        assert_eq!(*cell.state(), CellState::Covered);
+        assert_eq!(cell.neighboring_mines(), 2);
+        assert_eq!(cell.coords(), (0, 1));
+    }
+
+    #[test]
+    fn coords_and_indices_round_trip() {
+        for &(width, height) in [(1, 1), (3, 5), (8, 2), (30, 16)].iter() {
+            let grid = Grid::new(width, height);
+            for y in 0..height {
+                for x in 0..width {
+                    let index = grid.coord_to_index(x, y).unwrap();
+                    assert_eq!(index, (y * width + x) as usize);
+                    assert_eq!(grid.index_to_coord(index), Some((x, y)));
+                }
+            }
+            assert_eq!(grid.coord_to_index(-1, 0), None);
+            assert_eq!(grid.coord_to_index(0, -1), None);
+            assert_eq!(grid.coord_to_index(width, 0), None);
+            assert_eq!(grid.coord_to_index(0, height), None);
+            assert_eq!(grid.index_to_coord((width * height) as usize), None);
+        }
+    }
+
+    #[test]
+    fn cells_iterate_in_row_major_order() {
+        let mut grid = Grid::new(3, 2);
+        let coords = grid
+            .iter_cells()
+            .map(|(x, y, cell)| {
+                assert_eq!(cell.coords(), (x, y));
+                (x, y)
+            })
+            .collect::<Vec<(i32, i32)>>();
+        assert_eq!(coords, vec![(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1)]);
+        let mutable = grid
+            .iter_mut_cells()
+            .map(|(x, y, _)| (x, y))
+            .collect::<Vec<(i32, i32)>>();
+        assert_eq!(mutable, coords);
+    }
+
+    #[test]
+    fn boards_keep_one_safe_cell() {
+        assert!(Grid::try_with_mines(4, 4, 15).is_ok());
+        assert_eq!(
+            Grid::try_with_mines(4, 4, 16).err(),
+            Some(GridError::TooManyMines {
+                requested: 16,
+                capacity: 15
+            })
+        );
+        assert!(Grid::try_with_mines(1, 1, 0).is_ok());
+        assert!(Grid::try_with_mines(1, 1, 1).is_err());
+    }
+
+    #[test]
+    fn openings_are_revealed_breadth_first() {
+        let mut grid = Grid::with_mines(5, 5, &[(4, 4)]);
+        let (_, order) = grid.uncover_ordered(0, 0);
+        assert_eq!(order[0], (0, 0));
+        assert_eq!(order.len(), 24);
+        let distance = |&(x, y): &(i32, i32)| x.max(y);
+        for (i, cell) in order.iter().enumerate().skip(1) {
+            assert!(distance(&order[i - 1]) <= distance(cell));
+            // Each cell was reached from an earlier blank one
+            let reached_from = order[..i].iter().any(|&(x, y)| {
+                grid.get(x, y).unwrap().neighboring_mines() == 0
+                    && grid.neighbor_coords(x, y).contains(cell)
+            });
+            assert!(reached_from, "{:?} came from nowhere", cell);
+        }
+    }
+
+    #[test]
+    fn floods_count_down_the_safe_cells() {
+        // A wall of mines down the middle stops the flood at the 1s
+        let mut grid = Grid::with_mines(5, 3, &[(2, 0), (2, 1), (2, 2)]);
+        assert_eq!(grid.remaining_safe_cells(), 12);
+        grid.uncover(0, 1);
+        assert_eq!(grid.remaining_safe_cells(), 6);
+        grid.uncover(3, 1);
+        assert_eq!(grid.remaining_safe_cells(), 5);
+        grid.uncover(4, 1);
+        assert_eq!(grid.remaining_safe_cells(), 0);
+    }
+
+    #[test]
+    fn boards_render_the_solution_and_the_player_view() {
+        let mut grid = Grid::with_mines(4, 3, &[(0, 0), (3, 2)]);
+        assert_eq!(grid.to_string(), "% 1 0 0\n1 1 1 1\n0 0 1 %");
+        assert_eq!(grid.render_player_view(), "- - - -\n- - - -\n- - - -");
+        grid.uncover(2, 0);
+        grid.flag(0, 0);
+        grid.cycle_mark(3, 2);
+        grid.cycle_mark(3, 2);
+        assert_eq!(grid.render_player_view(), "F 1 0 0\n- 1 1 1\n- - - ?");
+        // The solution doesn't change as the board is played
+        assert_eq!(grid.to_string(), "% 1 0 0\n1 1 1 1\n0 0 1 %");
+    }
+
+    #[test]
+    fn one_click_stamps_its_whole_opening() {
+        let mut grid = Grid::with_mines(5, 3, &[(2, 0), (2, 1), (2, 2)]);
+        grid.set_clock(Duration::from_secs(1));
+        grid.uncover(0, 1);
+        grid.set_clock(Duration::from_secs(3));
+        grid.uncover(3, 1);
+        let timeline = grid
+            .reveal_timeline()
+            .iter()
+            .map(|cell| (cell.coords(), cell.revealed_at().unwrap().as_secs()))
+            .collect::<Vec<((i32, i32), u64)>>();
+        assert_eq!(
+            timeline,
+            vec![
+                ((0, 0), 1),
+                ((1, 0), 1),
+                ((0, 1), 1),
+                ((1, 1), 1),
+                ((0, 2), 1),
+                ((1, 2), 1),
+                ((3, 1), 3),
+            ]
+        );
+        assert_eq!(grid.get(4, 1).unwrap().revealed_at(), None);
+    }
+
+    #[test]
+    fn corners_have_three_neighbors_and_centers_eight() {
+        let grid = Grid::new(3, 3);
+        assert_eq!(grid.neighbor_coords(2, 2).len(), 3);
+        assert_eq!(grid.get_neighbors(0, 2).len(), 3);
+        let mut neighbors = grid.neighbor_coords(1, 1);
+        neighbors.sort();
+        let mut expected = vec![];
+        for x in 0..3 {
+            for y in 0..3 {
+                if (x, y) != (1, 1) {
+                    expected.push((x, y));
+                }
+            }
+        }
+        assert_eq!(neighbors, expected);
+        assert_eq!(grid.get_neighbors(1, 1).len(), 8);
+    }
+
+    #[test]
+    fn removing_a_placed_mine_restores_a_fresh_grid() {
+        let fresh = Grid::new(4, 4);
+        let mut grid = Grid::new(4, 4);
+        for &(x, y) in [(0, 0), (1, 2), (3, 3)].iter() {
+            assert!(grid.place_mine(x, y));
+            assert!(grid.remove_mine(x, y));
+            assert!(!grid.remove_mine(x, y));
+            assert_eq!(counts(&grid), counts(&fresh));
+            assert!(grid.mine_positions().is_empty());
+        }
+        assert_eq!(grid.mine_count(), 0);
+    }
+
+    #[test]
+    fn batches_stop_at_the_first_mine() {
+        let mut grid = Grid::with_mines(5, 1, &[(0, 0), (2, 0)]);
+        let batch = [(1, 0), (2, 0), (4, 0)];
+        assert_eq!(grid.uncover_many(&batch), BoardState::Detonated);
+        assert!(grid.get(1, 0).unwrap().is_exposed());
+        assert!(grid.get(4, 0).unwrap().is_covered());
+        assert!(grid.get(3, 0).unwrap().is_covered());
+
+        let mut grid = Grid::with_mines(5, 1, &[(0, 0), (2, 0)]);
+        assert_eq!(grid.uncover_many(&[(1, 0)]), BoardState::InProgress);
+        assert_eq!(grid.uncover_many(&[(4, 0)]), BoardState::Cleared);
+    }
+
+    #[test]
+    fn clones_play_independently() {
+        let mut grid = Grid::with_mines(5, 1, &[(0, 0)]);
+        grid.flag(0, 0);
+        let mut clone = grid.clone();
+        assert!(clone.get(0, 0).unwrap().is_flagged());
+        assert_eq!(clone.uncover(4, 0), BoardState::Cleared);
+        assert!(clone.is_won());
+        assert!(grid
+            .iter_cells()
+            .skip(1)
+            .all(|(_, _, cell)| cell.is_covered()));
+        assert_eq!(grid.remaining_safe_cells(), 4);
+        assert!(!grid.is_won());
+    }
+
+    #[test]
+    fn mine_positions_list_every_placed_mine() {
+        let mut grid = Grid::new(9, 9);
+        grid.populate_mines(10, &[], &mut StdRng::seed_from_u64(3));
+        let positions = grid.mine_positions();
+        assert_eq!(positions.len(), 10);
+        assert!(positions
+            .iter()
+            .all(|&(x, y)| grid.get(x, y).unwrap().has_mine));
+
+        let grid = Grid::with_mines(3, 3, &[(2, 2), (0, 1)]);
+        assert_eq!(grid.mine_positions(), vec![(0, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn wrong_flags_are_counted_apart_from_right_ones() {
+        let mut grid = Grid::with_mines(4, 4, &[(0, 0), (3, 3)]);
+        assert_eq!(grid.wrong_flag_count(), 0);
+        grid.flag(0, 0);
+        grid.flag(1, 2);
+        grid.flag(2, 1);
+        grid.cycle_mark(3, 0);
+        grid.cycle_mark(3, 0);
+        assert_eq!(grid.wrong_flag_count(), 2);
+        assert_eq!(grid.count_flags(), 3);
+
+        // Still counted once the loss marks them wrong
+        grid.reveal_on_loss();
+        assert_eq!(grid.wrong_flag_count(), 2);
+    }
+
+    #[test]
+    fn dense_boards_are_estimated_harder() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut sparse = Grid::new(9, 9);
+        sparse.populate_mines(5, &[], &mut rng);
+        let mut dense = Grid::new(9, 9);
+        dense.populate_mines(30, &[], &mut rng);
+        assert!(sparse.difficulty_estimate() > 0.);
+        assert!(sparse.difficulty_estimate() < dense.difficulty_estimate());
+        assert_eq!(Grid::new(9, 9).difficulty_estimate(), 0.);
+    }
+
+    #[test]
+    fn corrupted_counts_are_reported() {
+        let mut grid = Grid::with_mines(4, 4, &[(0, 0), (3, 3)]);
+        assert!(grid.validate().is_ok());
+        for (x, y, cell) in grid.iter_mut_cells() {
+            match (x, y) {
+                (1, 1) => cell.neighboring_mines = 0,
+                (3, 0) => cell.neighboring_mines = 2,
+                _ => {}
+            }
+        }
+        assert_eq!(grid.validate().err(), Some(vec![(3, 0), (1, 1)]));
+        grid.recompute_counts();
+        assert!(grid.validate().is_ok());
+    }
+
+    #[test]
+    fn listed_mines_count_their_neighbors() {
+        // * * . .
+        // * . . .
+        // . . . .
+        let grid = Grid::with_mines(4, 3, &[(0, 0), (1, 0), (0, 1)]);
+        assert_eq!(counts(&grid), vec![2, 2, 1, 0, 2, 3, 1, 0, 1, 1, 0, 0]);
+        assert_eq!(grid.mine_count(), 3);
+
+        let grid = Grid::with_mines(3, 3, &[(0, 0), (2, 0), (0, 2), (2, 2)]);
+        assert_eq!(counts(&grid), vec![0, 2, 0, 2, 4, 2, 0, 2, 0]);
+        assert_eq!(grid.mine_positions(), vec![(0, 0), (2, 0), (0, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn chords_skip_question_marks() {
+        // A question mark on the mine doesn't satisfy the 1
+        let mut grid = Grid::with_mines(5, 1, &[(0, 0)]);
+        grid.uncover(1, 0);
+        grid.cycle_mark(0, 0);
+        grid.cycle_mark(0, 0);
+        assert!(grid.get(0, 0).unwrap().is_questioned());
+        assert_eq!(grid.chord(1, 0), BoardState::InProgress);
+        assert!(grid.get(2, 0).unwrap().is_covered());
+
+        // A satisfied 1 leaves its questioned neighbor covered
+        let mut grid = Grid::with_mines(5, 1, &[(0, 0)]);
+        grid.uncover(1, 0);
+        grid.flag(0, 0);
+        grid.cycle_mark(2, 0);
+        grid.cycle_mark(2, 0);
+        assert_eq!(grid.chord(1, 0), BoardState::InProgress);
+        assert!(grid.get(2, 0).unwrap().is_questioned());
+        assert_eq!(grid.remaining_safe_cells(), 3);
+    }
+
+    #[test]
+    fn floods_match_a_plain_recursive_fill() {
+        fn reference(grid: &Grid, x: i32, y: i32, exposed: &mut Vec<(i32, i32)>) {
+            if exposed.contains(&(x, y)) || grid.get(x, y).unwrap().has_mine {
+                return;
+            }
+            exposed.push((x, y));
+            if grid.get(x, y).unwrap().neighboring_mines() == 0 {
+                for (i, j) in grid.neighbor_coords(x, y) {
+                    reference(grid, i, j, exposed);
+                }
+            }
+        }
+
+        let boards = [
+            Grid::with_mines(5, 5, &[(2, 2)]),
+            Grid::with_mines(6, 4, &[(0, 3), (3, 0), (3, 1), (5, 3)]),
+            Grid::with_mines(7, 3, &[(3, 0), (3, 1), (3, 2)]),
+        ];
+        for board in boards.iter() {
+            for &(x, y) in [(0, 0), (4, 1), (6, 2)].iter() {
+                if board.get(x, y).is_none_or(|cell| cell.has_mine) {
+                    continue;
+                }
+                let mut expected = vec![];
+                reference(board, x, y, &mut expected);
+                expected.sort();
+                let mut grid = board.clone();
+                grid.uncover(x, y);
+                let mut exposed = grid
+                    .iter_cells()
+                    .filter(|(_, _, cell)| cell.is_exposed())
+                    .map(|(x, y, _)| (x, y))
+                    .collect::<Vec<(i32, i32)>>();
+                exposed.sort();
+                assert_eq!(exposed, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn a_quarter_of_the_cells_are_mines() {
+        let mut grid = Grid::new(10, 10);
+        assert_eq!(grid.density(), 0.);
+        grid.populate_mines(25, &[], &mut StdRng::seed_from_u64(5));
+        assert_eq!(grid.density(), 0.25);
+    }
+
+    #[test]
+    fn boards_need_rows_and_columns() {
+        assert_eq!(
+            Grid::try_new(0, 5).err(),
+            Some(GridError::InvalidDimensions {
+                width: 0,
+                height: 5
+            })
+        );
+        assert_eq!(
+            Grid::try_new(5, -1).err(),
+            Some(GridError::InvalidDimensions {
+                width: 5,
+                height: -1
+            })
+        );
+        assert!(Grid::try_new(1, 1).is_ok());
+        assert!(Grid::try_with_mines(0, 0, 0).is_err());
+    }
+
+    #[test]
+    fn flags_around_a_cell_are_counted() {
+        let mut grid = Grid::with_mines(3, 3, &[(0, 0), (2, 2)]);
+        assert_eq!(grid.neighboring_flags(1, 1), 0);
+        grid.flag(0, 0);
+        grid.flag(2, 1);
+        // Question marks and flags further away don't count
+        grid.cycle_mark(1, 0);
+        grid.cycle_mark(1, 0);
+        assert_eq!(grid.neighboring_flags(1, 1), 2);
+        assert_eq!(grid.neighboring_flags(0, 2), 0);
+    }
+
+    #[test]
+    fn predicates_cover_every_state() {
+        // (state, exposed, revealed, flagged, covered, questioned)
+        let table = [
+            (CellState::Covered, false, false, false, true, false),
+            (CellState::Exposed, true, true, false, false, false),
+            (CellState::Flagged, false, false, true, false, false),
+            (CellState::Questioned, false, false, false, false, true),
+            (CellState::Detonated, false, true, false, false, false),
+            (CellState::WronglyFlagged, false, false, false, false, false),
+        ];
+        let mut grid = Grid::new(1, 1);
+        for &(state, exposed, revealed, flagged, covered, questioned) in table.iter() {
+            for (_, _, cell) in grid.iter_mut_cells() {
+                cell.state = state;
+            }
+            let cell = grid.get(0, 0).unwrap();
+            assert_eq!(cell.is_exposed(), exposed, "{:?}", state);
+            assert_eq!(cell.is_revealed(), revealed, "{:?}", state);
+            assert_eq!(cell.is_flagged(), flagged, "{:?}", state);
+            assert_eq!(cell.is_covered(), covered, "{:?}", state);
+            assert_eq!(cell.is_questioned(), questioned, "{:?}", state);
+        }
+    }
+
+    #[test]
+    fn flags_recount_after_flagging_every_mine() {
+        let mut grid = Grid::with_mines(4, 4, &[(0, 0), (1, 3), (3, 2)]);
+        grid.flag(0, 0);
+        grid.flag(2, 2);
+        assert_eq!(grid.count_flags(), 2);
+        // Only the two unflagged mines are new flags
+        assert_eq!(grid.flag_all_mines(), 2);
+        assert_eq!(grid.count_flags(), 4);
+        assert_eq!(grid.flag_all_mines(), 0);
+        assert_eq!(grid.count_flags(), 4);
+    }
+
+    #[test]
+    fn depth_one_reveals_only_the_first_ring() {
+        let mut grid = Grid::with_mines(7, 7, &[(6, 6)]);
+        let (board_state, mut exposed) = grid.uncover_to_depth(2, 2, Some(1));
+        assert_eq!(board_state, BoardState::InProgress);
+        exposed.sort();
+        let mut ring = vec![];
+        for x in 1..4 {
+            for y in 1..4 {
+                ring.push((x, y));
+            }
+        }
+        assert_eq!(exposed, ring);
+        assert_eq!(grid.remaining_safe_cells(), 48 - 9);
+        assert!(grid.get(4, 2).unwrap().is_covered());
+
+        // Without a limit the same click clears the board
+        let mut grid = Grid::with_mines(7, 7, &[(6, 6)]);
+        assert_eq!(grid.uncover_to_depth(2, 2, None).0, BoardState::Cleared);
+    }
+
+    #[test]
+    fn numbers_are_satisfied_by_exactly_their_count() {
+        let mut grid = Grid::with_mines(3, 3, &[(0, 0), (2, 0)]);
+        assert!(!grid.is_number_satisfied(1, 1));
+        grid.uncover(1, 1);
+        grid.flag(0, 0);
+        assert!(!grid.is_number_satisfied(1, 1));
+        grid.flag(2, 0);
+        assert!(grid.is_number_satisfied(1, 1));
+        grid.flag(1, 0);
+        assert!(!grid.is_number_satisfied(1, 1));
+    }
+
+    #[test]
+    fn chording_all_follows_a_chain_to_the_end() {
+        // * * * * *
+        // 2 3 3 3 2
+        let mines = [(0, 0), (1, 0), (2, 0), (3, 0), (4, 0)];
+        let mut grid = Grid::with_mines(5, 2, &mines);
+        for &(x, y) in mines.iter() {
+            grid.flag(x, y);
+        }
+        grid.uncover(0, 1);
+        // Each chord only opens the next number along the row
+        assert_eq!(grid.remaining_safe_cells(), 4);
+        assert_eq!(grid.auto_chord_all(), BoardState::Cleared);
+        assert!(grid.is_won());
+        // Nothing left to chord, but the board is still cleared
+        assert_eq!(grid.auto_chord_all(), BoardState::Cleared);
     }
 }
@@ -0,0 +1,179 @@
+use std::{collections::HashMap, str::FromStr};
+
+use ggez::event::KeyCode;
+
+/** Game actions that can be bound to a key */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum KeyAction {
+    Reveal,
+    Flag,
+    Chord,
+    Restart,
+    Hint,
+    Pause,
+}
+
+impl FromStr for KeyAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "reveal" => Ok(KeyAction::Reveal),
+            "flag" => Ok(KeyAction::Flag),
+            "chord" => Ok(KeyAction::Chord),
+            "restart" => Ok(KeyAction::Restart),
+            "hint" => Ok(KeyAction::Hint),
+            "pause" => Ok(KeyAction::Pause),
+            _ => Err(format!("unknown action '{}'", s)),
+        }
+    }
+}
+
+/** Which keys trigger each action. An action can have several keys, but
+each key triggers one action at most. Keys held with Ctrl are left to the
+fixed shortcuts */
+pub struct KeyMap {
+    bindings: HashMap<KeyCode, KeyAction>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        KeyMap::new(&[
+            (KeyAction::Reveal, KeyCode::Space),
+            (KeyAction::Reveal, KeyCode::Return),
+            (KeyAction::Flag, KeyCode::F),
+            (KeyAction::Chord, KeyCode::E),
+            (KeyAction::Restart, KeyCode::R),
+            (KeyAction::Hint, KeyCode::H),
+            (KeyAction::Pause, KeyCode::P),
+        ])
+    }
+}
+
+impl KeyMap {
+    pub fn new(bindings: &[(KeyAction, KeyCode)]) -> Self {
+        KeyMap {
+            bindings: bindings
+                .iter()
+                .map(|&(action, key)| (key, action))
+                .collect(),
+        }
+    }
+
+    /** Rebinds `action` to just `keys`. Any other action bound to one of
+    them loses it */
+    pub fn with(mut self, action: KeyAction, keys: &[KeyCode]) -> Self {
+        self.bindings.retain(|_, bound| *bound != action);
+        for &key in keys {
+            self.bindings.insert(key, action);
+        }
+        self
+    }
+
+    /** The action bound to `key`, if any */
+    pub fn action(&self, key: KeyCode) -> Option<KeyAction> {
+        self.bindings.get(&key).copied()
+    }
+
+    /** Every key bound to `action` */
+    #[cfg(test)]
+    fn keys(&self, action: KeyAction) -> Vec<KeyCode> {
+        let mut keys = self
+            .bindings
+            .iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(key, _)| *key)
+            .collect::<Vec<KeyCode>>();
+        keys.sort_by_key(|key| *key as u32);
+        keys
+    }
+}
+
+/** Parses a binding like `flag=g`, or `reveal=space,return` for several
+keys. Only letter keys, `space` and `return` can be named */
+pub fn parse_binding(binding: &str) -> Result<(KeyAction, Vec<KeyCode>), String> {
+    let (action, keys) = binding
+        .split_once('=')
+        .ok_or_else(|| format!("expected action=key, got '{}'", binding))?;
+    let keys = keys
+        .split(',')
+        .map(parse_key)
+        .collect::<Result<Vec<KeyCode>, String>>()?;
+    Ok((action.parse()?, keys))
+}
+
+fn parse_key(key: &str) -> Result<KeyCode, String> {
+    let key = match key.to_lowercase().as_str() {
+        "space" => KeyCode::Space,
+        "return" | "enter" => KeyCode::Return,
+        "a" => KeyCode::A,
+        "b" => KeyCode::B,
+        "c" => KeyCode::C,
+        "d" => KeyCode::D,
+        "e" => KeyCode::E,
+        "f" => KeyCode::F,
+        "g" => KeyCode::G,
+        "h" => KeyCode::H,
+        "i" => KeyCode::I,
+        "j" => KeyCode::J,
+        "k" => KeyCode::K,
+        "l" => KeyCode::L,
+        "m" => KeyCode::M,
+        "n" => KeyCode::N,
+        "o" => KeyCode::O,
+        "p" => KeyCode::P,
+        "q" => KeyCode::Q,
+        "r" => KeyCode::R,
+        "s" => KeyCode::S,
+        "t" => KeyCode::T,
+        "u" => KeyCode::U,
+        "v" => KeyCode::V,
+        "w" => KeyCode::W,
+        "x" => KeyCode::X,
+        "y" => KeyCode::Y,
+        "z" => KeyCode::Z,
+        _ => return Err(format!("unknown key '{}'", key)),
+    };
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_keys() {
+        let keymap = KeyMap::default();
+        assert_eq!(keymap.action(KeyCode::Space), Some(KeyAction::Reveal));
+        assert_eq!(keymap.action(KeyCode::Return), Some(KeyAction::Reveal));
+        assert_eq!(keymap.action(KeyCode::F), Some(KeyAction::Flag));
+        assert_eq!(keymap.action(KeyCode::P), Some(KeyAction::Pause));
+        assert_eq!(keymap.action(KeyCode::Q), None);
+    }
+
+    #[test]
+    fn bindings_override_the_defaults() {
+        let (action, keys) = parse_binding("flag=g").unwrap();
+        let keymap = KeyMap::default().with(action, &keys);
+        assert_eq!(keymap.action(KeyCode::G), Some(KeyAction::Flag));
+        assert_eq!(keymap.action(KeyCode::F), None);
+
+        // Taking another action's key leaves it the rest of its keys
+        let (action, keys) = parse_binding("Chord=Return,c").unwrap();
+        let keymap = keymap.with(action, &keys);
+        assert_eq!(keymap.keys(KeyAction::Reveal), vec![KeyCode::Space]);
+        assert_eq!(
+            keymap.keys(KeyAction::Chord),
+            vec![KeyCode::C, KeyCode::Return]
+        );
+        assert_eq!(keymap.action(KeyCode::E), None);
+    }
+
+    #[test]
+    fn malformed_bindings_are_rejected() {
+        assert!(parse_binding("flag").is_err());
+        assert!(parse_binding("fly=g").is_err());
+        assert!(parse_binding("flag=f1").is_err());
+        assert!(parse_binding("flag=g,").is_err());
+    }
+}
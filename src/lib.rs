@@ -0,0 +1,5 @@
+//! Game logic for minesweeper, usable without a window or renderer.
+
+pub mod base64;
+pub mod grid;
+pub mod solver;
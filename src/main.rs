@@ -1,5 +1,8 @@
+mod audio;
+mod clipboard;
 mod game;
-mod grid;
+mod keymap;
+mod scores;
 
 use ggez::{
     conf::{WindowMode, WindowSetup},
@@ -8,78 +11,275 @@ use ggez::{
     Context, ContextBuilder, GameError,
 };
 
-use std::{fmt, path::Path, time::Duration};
+use rand::{rngs::StdRng, SeedableRng};
+
+use std::{env, fs, path::Path, process};
+
+use minesweeper::grid::{Grid, NeighborMode, Topology};
 
 use crate::{
-    game::{GameState, PlayState, UI_SCALE},
-    grid::Grid,
+    audio::Audio,
+    game::{Difficulty, GameState, SpriteConfig, WinCondition, DEFAULT_UI_SCALE},
+    keymap::{parse_binding, KeyMap},
 };
 
+/** Where the sprites are read from, inside the `assets` folder */
+const SPRITESHEET_PATH: &str = "/minesweeper.png";
+
 fn main() -> Result<(), GameError> {
-    let (ref mut ctx, ref mut event_loop) = ContextBuilder::new("minesweeper", "")
-        .window_setup(WindowSetup::default().title("minesweeper"))
-        .window_mode(WindowMode::default().dimensions(256. * UI_SCALE, (256. + 24.0) * UI_SCALE))
-        .add_resource_path("assets")
-        .build()
-        .unwrap();
-    let spritesheet = load_spritesheet(ctx, "/minesweeper.png", 8, 8, 4)?;
-
-    let width = 32;
-    let height = 32;
-    let mut grid = Grid::new(width, height);
-    let mut mine_count = 0;
-    for x in 0..width {
-        for y in 0..height {
-            if rand::random::<f32>() > 0.85 {
-                // TODO: Mines can spawn on the same position and the count
-                // would become incorrect
-                grid.place_mine(x, y);
-                mine_count += 1;
+    let mut difficulty = Difficulty::default();
+    let mut seed = rand::random::<u64>();
+    let mut board = None;
+    let mut no_guess = false;
+    let mut training = false;
+    let mut read_only = false;
+    let mut win_condition = WinCondition::default();
+    let mut reveal_depth = None;
+    let mut versus = false;
+    let mut wrap = false;
+    let mut topology = Topology::Square;
+    let mut neighbor_mode = NeighborMode::Eight;
+    let mut keymap = KeyMap::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--difficulty" => {
+                if let Some(value) = args.next() {
+                    difficulty = value.parse().map_err(GameError::ConfigError)?;
+                }
             }
+            "--seed" => {
+                if let Some(value) = args.next() {
+                    seed = value
+                        .parse()
+                        .map_err(|_| GameError::ConfigError(format!("invalid seed '{}'", value)))?;
+                }
+            }
+            "--board" => board = args.next(),
+            "--reveal-depth" => {
+                if let Some(value) = args.next() {
+                    reveal_depth = Some(value.parse().map_err(|_| {
+                        GameError::ConfigError(format!("invalid reveal depth '{}'", value))
+                    })?);
+                }
+            }
+            "--no-guess" => no_guess = true,
+            "--training" => training = true,
+            "--read-only" => read_only = true,
+            "--flag-to-win" => win_condition = WinCondition::FlagAllMines,
+            "--versus" => versus = true,
+            "--wrap" => wrap = true,
+            "--hex" => topology = Topology::Hex,
+            "--orthogonal" => neighbor_mode = NeighborMode::Four,
+            "--bind" => {
+                if let Some(value) = args.next() {
+                    let (action, keys) = parse_binding(&value).map_err(GameError::ConfigError)?;
+                    keymap = keymap.with(action, &keys);
+                }
+            }
+            _ => {}
         }
     }
+    if versus && (training || read_only) {
+        return Err(GameError::ConfigError(
+            "--versus can't be combined with --training or --read-only".to_string(),
+        ));
+    }
+    let (width, height) = difficulty.dimensions();
+    let mut mine_count = difficulty.mine_count();
+    let mut grid = Grid::try_with_mines(width, height, mine_count)
+        .map_err(|err| GameError::ConfigError(err.to_string()))?;
+    let mut title = format!("minesweeper (seed {})", seed);
+    if let Some(path) = board {
+        let layout = fs::read_to_string(&path)
+            .map_err(|err| GameError::ConfigError(format!("could not read {}: {}", path, err)))?;
+        grid = Grid::from_ascii(&layout)
+            .map_err(|err| GameError::ConfigError(format!("{}: {}", path, err)))?;
+        mine_count = grid.mine_count();
+        title = format!(
+            "minesweeper ({}, {:.0}% mines)",
+            path,
+            grid.density() * 100.
+        );
+    }
+    // Loaded boards get the same shape options as generated ones
+    if wrap {
+        grid.set_wrap(true);
+    }
+    if topology != Topology::Square {
+        grid.set_topology(topology);
+    }
+    if neighbor_mode != NeighborMode::Eight {
+        grid.set_neighbor_mode(neighbor_mode);
+    }
 
-    let state = &mut GameState::new(mine_count, grid, spritesheet);
-    event::run(ctx, event_loop, state).unwrap();
+    let players = if versus { 2 } else { 1 };
+    let sprites = SpriteConfig::default();
+    let (board_width, window_height) = sprites.window_size(&grid, DEFAULT_UI_SCALE);
+    let window_width = board_width * players as f32;
+    let (ref mut ctx, ref mut event_loop) = ContextBuilder::new("minesweeper", "")
+        .window_setup(WindowSetup::default().title(&title))
+        .window_mode(WindowMode::default().dimensions(window_width, window_height))
+        .add_resource_path("assets")
+        .build()?;
+    let spritesheet = match load_spritesheet(
+        ctx,
+        SPRITESHEET_PATH,
+        sprites.cell_px as usize,
+        sprites.cell_px as usize,
+        4,
+        5,
+    ) {
+        Err(GameError::ResourceNotFound(..)) => {
+            eprintln!("{}", missing_asset_message(SPRITESHEET_PATH));
+            process::exit(1);
+        }
+        result => result?,
+    };
+    let audio = Audio::load(ctx);
+
+    let state = &mut GameState::new(
+        mine_count,
+        grid,
+        StdRng::seed_from_u64(seed),
+        spritesheet,
+        audio,
+    );
+    state.no_guess = no_guess;
+    state.training = training;
+    state.read_only = read_only;
+    state.win_condition = win_condition;
+    state.reveal_depth = reveal_depth;
+    state.title = title;
+    state.keymap = keymap;
+    if versus {
+        state.race(players);
+    }
+    event::run(ctx, event_loop, state)
+}
 
-    Ok(())
+/** What to tell the player when the asset at `path` can't be found */
+fn missing_asset_message(path: &str) -> String {
+    let file = path.trim_start_matches('/');
+    format!(
+        "could not find {}: place {} in ./assets next to the game",
+        file, file
+    )
 }
 
+/** Cuts the image at `path` into a grid of `columns` by `rows` sprites,
+returned in row-major order */
 fn load_spritesheet(
     ctx: &mut Context,
     path: &str,
     sprite_width: usize,
     sprite_height: usize,
-    horizontal_sprite_count: usize,
+    columns: usize,
+    rows: usize,
 ) -> Result<Vec<Image>, GameError> {
-    let sprite_size = sprite_width * sprite_height;
-    let sprite_row_size = sprite_width * horizontal_sprite_count;
-
-    // Load and split image into a Vec of sprites
-    Ok(Image::new(ctx, Path::new(path))?
-        .to_rgba8(ctx)?
-        // Split pixel data into sprite rows
-        .chunks(sprite_size * horizontal_sprite_count * 4)
-        .flat_map(|row| {
-            row
-                // Split pixel data into sprite row cross-sections
-                .chunks(sprite_row_size)
-                .enumerate()
-                // Merge rows into sprite pixel data
-                .fold(
-                    vec![vec![]; horizontal_sprite_count],
-                    |mut sprites, (i, row)| {
-                        sprites[i % horizontal_sprite_count].extend_from_slice(row);
-                        sprites
-                    },
-                )
-        })
-        // Transform sprite pixel data into images
+    let image = Image::new(ctx, Path::new(path))?;
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let sprites = split_sprites(
+        &image.to_rgba8(ctx)?,
+        (width, height),
+        (sprite_width, sprite_height),
+        (columns, rows),
+    )
+    .map_err(|err| GameError::ResourceLoadError(format!("{}: {}", path, err)))?;
+    sprites
+        .iter()
         .map(|pixels| {
             let mut sprite =
-                Image::from_rgba8(ctx, sprite_width as u16, sprite_height as u16, &pixels).unwrap();
+                Image::from_rgba8(ctx, sprite_width as u16, sprite_height as u16, pixels)?;
             sprite.set_filter(FilterMode::Nearest);
-            sprite
+            Ok(sprite)
+        })
+        .collect()
+}
+
+/** Splits RGBA `pixels` of a `width` by `height` image into `columns` by
+`rows` sprites, returned in row-major order. Fails unless the image is
+exactly that many sprites of `sprite_width` by `sprite_height` */
+fn split_sprites(
+    pixels: &[u8],
+    (width, height): (usize, usize),
+    (sprite_width, sprite_height): (usize, usize),
+    (columns, rows): (usize, usize),
+) -> Result<Vec<Vec<u8>>, String> {
+    if width != sprite_width * columns || height != sprite_height * rows {
+        return Err(format!(
+            "the image is {}x{}, which doesn't split into {}x{} sprites of {}x{}",
+            width, height, columns, rows, sprite_width, sprite_height
+        ));
+    }
+    if pixels.len() != width * height * 4 {
+        return Err(format!(
+            "the image decoded to {} bytes, expected {}",
+            pixels.len(),
+            width * height * 4
+        ));
+    }
+
+    let sprites = (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (row, column)))
+        .map(|(row, column)| {
+            // Copy the sprite out of the sheet one line of pixels at a time
+            let mut sprite_pixels = Vec::with_capacity(sprite_width * sprite_height * 4);
+            for line in 0..sprite_height {
+                let start = ((row * sprite_height + line) * width + column * sprite_width) * 4;
+                sprite_pixels.extend_from_slice(&pixels[start..start + sprite_width * 4]);
+            }
+            sprite_pixels
         })
-        .collect::<Vec<Image>>())
+        .collect();
+    Ok(sprites)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spritesheets_must_split_evenly() {
+        // A 4x2 sheet of two 2x2 sprites, each pixel's red channel its index
+        let pixels = (0..8u8).flat_map(|i| [i, 0, 0, 255]).collect::<Vec<u8>>();
+        let sprites = split_sprites(&pixels, (4, 2), (2, 2), (2, 1)).unwrap();
+        let reds = sprites
+            .iter()
+            .map(|sprite| sprite.chunks(4).map(|pixel| pixel[0]).collect())
+            .collect::<Vec<Vec<u8>>>();
+        assert_eq!(reds, vec![vec![0, 1, 4, 5], vec![2, 3, 6, 7]]);
+
+        assert!(split_sprites(&pixels, (4, 2), (3, 2), (2, 1)).is_err());
+        assert!(split_sprites(&pixels, (4, 2), (2, 2), (2, 2)).is_err());
+        assert!(split_sprites(&pixels[..28], (4, 2), (2, 2), (2, 1)).is_err());
+    }
+
+    #[test]
+    fn sprites_come_out_row_by_row() {
+        // A 4x4 sheet of four 2x2 sprites in two rows
+        let pixels = (0..16u8).flat_map(|i| [i, 0, 0, 255]).collect::<Vec<u8>>();
+        let sprites = split_sprites(&pixels, (4, 4), (2, 2), (2, 2)).unwrap();
+        let reds = sprites
+            .iter()
+            .map(|sprite| sprite.chunks(4).map(|pixel| pixel[0]).collect())
+            .collect::<Vec<Vec<u8>>>();
+        assert_eq!(
+            reds,
+            vec![
+                vec![0, 1, 4, 5],
+                vec![2, 3, 6, 7],
+                vec![8, 9, 12, 13],
+                vec![10, 11, 14, 15],
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_assets_say_where_they_go() {
+        assert_eq!(
+            missing_asset_message("/minesweeper.png"),
+            "could not find minesweeper.png: place minesweeper.png in ./assets next to the game"
+        );
+    }
 }
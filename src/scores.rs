@@ -0,0 +1,104 @@
+use std::{collections::HashMap, fs, io, time::Duration};
+
+use crate::game::Difficulty;
+
+/** Fastest winning time for each difficulty, kept on disk as a flat JSON
+object of milliseconds like `{"beginner": 41250}` */
+#[derive(Default)]
+pub struct Scores {
+    best: HashMap<Difficulty, Duration>,
+}
+
+impl Scores {
+    /** Reads the scores at `path`. A missing or unreadable file starts a
+    fresh table instead of failing */
+    pub fn load(path: &str) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| Scores::parse(&contents))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let entries = Difficulty::ALL
+            .iter()
+            .filter_map(|difficulty| {
+                self.best
+                    .get(difficulty)
+                    .map(|time| format!("\"{}\": {}", difficulty, time.as_millis()))
+            })
+            .collect::<Vec<String>>();
+        fs::write(path, format!("{{{}}}\n", entries.join(", ")))
+    }
+
+    pub fn best(&self, difficulty: Difficulty) -> Option<Duration> {
+        self.best.get(&difficulty).copied()
+    }
+
+    /** Keeps `time` if it beats the current best. Returns whether it did */
+    pub fn record(&mut self, difficulty: Difficulty, time: Duration) -> bool {
+        match self.best(difficulty) {
+            Some(best) if best <= time => false,
+            _ => {
+                self.best.insert(difficulty, time);
+                true
+            }
+        }
+    }
+
+    fn parse(contents: &str) -> Option<Self> {
+        let body = contents.trim().strip_prefix('{')?.strip_suffix('}')?.trim();
+        let mut best = HashMap::new();
+        if body.is_empty() {
+            return Some(Scores { best });
+        }
+        for entry in body.split(',') {
+            let (key, value) = entry.split_once(':')?;
+            let difficulty = key
+                .trim()
+                .strip_prefix('"')?
+                .strip_suffix('"')?
+                .parse::<Difficulty>()
+                .ok()?;
+            let millis = value.trim().parse::<u64>().ok()?;
+            best.insert(difficulty, Duration::from_millis(millis));
+        }
+        Some(Scores { best })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn scores_round_trip_and_survive_bad_files() {
+        let path = env::temp_dir().join(format!("minesweeper-scores-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut scores = Scores::default();
+        assert!(scores.record(Difficulty::Beginner, Duration::from_millis(41250)));
+        assert!(!scores.record(Difficulty::Beginner, Duration::from_secs(50)));
+        assert!(scores.record(Difficulty::Expert, Duration::from_secs(200)));
+        scores.save(path).unwrap();
+        let loaded = Scores::load(path);
+        assert_eq!(
+            loaded.best(Difficulty::Beginner),
+            Some(Duration::from_millis(41250))
+        );
+        assert_eq!(loaded.best(Difficulty::Intermediate), None);
+        assert_eq!(
+            loaded.best(Difficulty::Expert),
+            Some(Duration::from_secs(200))
+        );
+
+        for contents in ["{\"beginner\": fast}", "{\"novice\": 1}", "[1, 2]", ""].iter() {
+            fs::write(path, contents).unwrap();
+            assert_eq!(Scores::load(path).best(Difficulty::Beginner), None);
+        }
+        fs::remove_file(path).unwrap();
+        assert_eq!(Scores::load(path).best(Difficulty::Beginner), None);
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::grid::{CellState, CellView, Grid};
+
+/** Largest group of linked frontier cells whose mine layouts are
+enumerated. Bigger groups fall back to the overall mine density */
+const ENUMERATION_LIMIT: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Flag(i32, i32),
+    Reveal(i32, i32),
+}
+
+/** Finds every move that follows from a single exposed number, using only
+what the player can see:
+- if a number equals its count of unflagged covered neighbors plus flags,
+  every covered neighbor is a mine
+- if a number equals its count of flagged neighbors, every other covered
+  neighbor is safe */
+pub fn deduce(grid: &Grid) -> Vec<Action> {
+    let cells = grid.snapshot();
+    let mut seen = HashSet::new();
+    let mut actions = vec![];
+
+    for cell in cells.iter() {
+        let count = match cell.neighboring_mines {
+            Some(count) if count > 0 => count as usize,
+            _ => continue,
+        };
+        let neighbors = neighbors(grid, &cells, cell.x, cell.y);
+        let flagged = grid.neighboring_flags(cell.x, cell.y) as usize;
+        let unknown = neighbors
+            .iter()
+            .filter(|neighbor| is_unknown(neighbor))
+            .collect::<Vec<&&CellView>>();
+        if unknown.is_empty() {
+            continue;
+        }
+
+        let action = if flagged + unknown.len() == count {
+            Action::Flag
+        } else if flagged == count {
+            Action::Reveal
+        } else {
+            continue;
+        };
+        for neighbor in unknown {
+            let action = action(neighbor.x, neighbor.y);
+            if seen.insert(action) {
+                actions.push(action);
+            }
+        }
+    }
+    actions
+}
+
+/** Chance that each covered, unflagged cell holds a mine, assuming every
+flag is right. Cells next to exposed numbers are worked out by trying every
+layout of mines around them that fits the numbers, with each layout counted
+as equally likely. The rest share the mines left over evenly */
+pub fn probabilities(grid: &Grid) -> HashMap<(i32, i32), f32> {
+    let cells = grid.snapshot();
+    let flags = cells
+        .iter()
+        .filter(|cell| cell.state == CellState::Flagged)
+        .count() as i32;
+    let probabilities = enumerated(grid);
+    let expected_mines = probabilities.values().sum::<f32>();
+
+    let unsettled = cells
+        .iter()
+        .filter(|cell| is_unknown(cell) && !probabilities.contains_key(&(cell.x, cell.y)))
+        .count();
+    let density = if unsettled > 0 {
+        ((grid.mine_count() - flags) as f32 - expected_mines) / unsettled as f32
+    } else {
+        0.
+    };
+    cells
+        .iter()
+        .filter(|cell| is_unknown(cell))
+        .map(|cell| {
+            let chance = probabilities.get(&(cell.x, cell.y)).copied();
+            ((cell.x, cell.y), chance.unwrap_or(density).clamp(0., 1.))
+        })
+        .collect()
+}
+
+/** The part of `probabilities` that comes from trying every layout: only
+covered cells next to exposed numbers, in groups small enough to enumerate.
+A chance of 0 here means no layout that fits the numbers has a mine there,
+which the overall density can't promise */
+pub fn enumerated(grid: &Grid) -> HashMap<(i32, i32), f32> {
+    let cells = grid.snapshot();
+    let index_of = |cell: &CellView| grid.coord_to_index(cell.x, cell.y).unwrap();
+
+    // Each exposed number becomes a constraint on its unknown neighbors
+    let mut constraints = vec![];
+    for cell in cells.iter() {
+        let count = match (cell.neighboring_mines, cell.has_mine) {
+            (Some(count), Some(false)) => count as i32,
+            _ => continue,
+        };
+        let neighbors = neighbors(grid, &cells, cell.x, cell.y);
+        let flagged = grid.neighboring_flags(cell.x, cell.y) as i32;
+        let unknown = neighbors
+            .iter()
+            .filter(|neighbor| is_unknown(neighbor))
+            .map(|neighbor| index_of(neighbor))
+            .collect::<Vec<usize>>();
+        if !unknown.is_empty() {
+            constraints.push((unknown, count - flagged));
+        }
+    }
+    let mut touching = HashMap::<usize, Vec<usize>>::new();
+    for (constraint, (unknown, _)) in constraints.iter().enumerate() {
+        for &index in unknown {
+            touching.entry(index).or_default().push(constraint);
+        }
+    }
+
+    // Split the frontier into groups that share no constraints, so each can
+    // be enumerated on its own
+    let mut probabilities = HashMap::new();
+    let mut visited = HashSet::new();
+    for &start in touching.keys() {
+        if !visited.insert(start) {
+            continue;
+        }
+        let mut group = vec![start];
+        let mut group_constraints = HashSet::new();
+        let mut next = 0;
+        while next < group.len() {
+            for &constraint in &touching[&group[next]] {
+                if group_constraints.insert(constraint) {
+                    for &index in &constraints[constraint].0 {
+                        if visited.insert(index) {
+                            group.push(index);
+                        }
+                    }
+                }
+            }
+            next += 1;
+        }
+        if group.len() > ENUMERATION_LIMIT {
+            continue;
+        }
+        let group_constraints = group_constraints
+            .into_iter()
+            .map(|constraint| &constraints[constraint])
+            .collect::<Vec<&(Vec<usize>, i32)>>();
+        if let Some(chances) = enumerate(&group, &group_constraints) {
+            for (&index, chance) in group.iter().zip(chances) {
+                let cell = &cells[index];
+                probabilities.insert((cell.x, cell.y), chance);
+            }
+        }
+    }
+    probabilities
+}
+
+/** Tries every way of placing mines on `group` that satisfies all of
+`constraints`, returning how often each cell held a mine. `None` if no
+layout fits */
+fn enumerate(group: &[usize], constraints: &[&(Vec<usize>, i32)]) -> Option<Vec<f32>> {
+    fn fits(constraints: &[&(Vec<usize>, i32)], layout: &HashMap<usize, bool>) -> bool {
+        constraints.iter().all(|(unknown, needed)| {
+            let mines = unknown
+                .iter()
+                .filter(|index| layout.get(index) == Some(&true))
+                .count();
+            let open = unknown
+                .iter()
+                .filter(|index| !layout.contains_key(index))
+                .count();
+            mines as i32 <= *needed && (mines + open) as i32 >= *needed
+        })
+    }
+
+    fn search(
+        group: &[usize],
+        constraints: &[&(Vec<usize>, i32)],
+        layout: &mut HashMap<usize, bool>,
+        hits: &mut [u64],
+        layouts: &mut u64,
+    ) {
+        let next = layout.len();
+        if next == group.len() {
+            *layouts += 1;
+            for (hit, index) in hits.iter_mut().zip(group) {
+                if layout[index] {
+                    *hit += 1;
+                }
+            }
+            return;
+        }
+        for mine in [false, true] {
+            layout.insert(group[next], mine);
+            if fits(constraints, layout) {
+                search(group, constraints, layout, hits, layouts);
+            }
+            layout.remove(&group[next]);
+        }
+    }
+
+    let mut hits = vec![0; group.len()];
+    let mut layouts = 0;
+    search(
+        group,
+        constraints,
+        &mut HashMap::new(),
+        &mut hits,
+        &mut layouts,
+    );
+    if layouts == 0 {
+        return None;
+    }
+    Some(
+        hits.iter()
+            .map(|&hit| hit as f32 / layouts as f32)
+            .collect(),
+    )
+}
+
+fn is_unknown(cell: &CellView) -> bool {
+    cell.state == CellState::Covered || cell.state == CellState::Questioned
+}
+
+fn neighbors<'a>(grid: &Grid, cells: &'a [CellView], x: i32, y: i32) -> Vec<&'a CellView> {
+    grid.neighbor_coords(x, y)
+        .into_iter()
+        .map(|(x, y)| &cells[grid.coord_to_index(x, y).unwrap()])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_numbers_settle_their_neighbors() {
+        // A 1 with one covered neighbor: that neighbor is a mine
+        let mut grid = Grid::with_mines(2, 1, &[(0, 0)]);
+        grid.uncover(1, 0);
+        assert_eq!(deduce(&grid), vec![Action::Flag(0, 0)]);
+
+        // A 1 with its mine flagged: the other neighbor is safe
+        let mut grid = Grid::with_mines(3, 1, &[(0, 0)]);
+        grid.uncover(1, 0);
+        assert_eq!(deduce(&grid), vec![]);
+        grid.flag(0, 0);
+        assert_eq!(deduce(&grid), vec![Action::Reveal(2, 0)]);
+
+        // Only what's visible counts, so a wrong flag is trusted
+        let mut grid = Grid::with_mines(3, 1, &[(0, 0)]);
+        grid.uncover(1, 0);
+        grid.flag(2, 0);
+        assert_eq!(deduce(&grid), vec![Action::Reveal(0, 0)]);
+    }
+
+    #[test]
+    fn chances_match_hand_counts() {
+        // * . *
+        // 1 2 1
+        let mut grid = Grid::with_mines(3, 2, &[(0, 0), (2, 0)]);
+        grid.uncover_many(&[(0, 1), (1, 1), (2, 1)]);
+        let chances = probabilities(&grid);
+        assert_eq!(chances.len(), 3);
+        for &(coords, chance) in [((0, 0), 1.), ((1, 0), 0.), ((2, 0), 1.)].iter() {
+            assert!((chances[&coords] - chance).abs() < 1e-6);
+        }
+
+        // Nothing uncovered, so every cell gets the board's density
+        let grid = Grid::with_mines(4, 4, &[(0, 0), (3, 3)]);
+        let chances = probabilities(&grid);
+        assert_eq!(chances.len(), 16);
+        assert!(chances.values().all(|chance| (chance - 0.125).abs() < 1e-6));
+    }
+}
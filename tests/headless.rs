@@ -0,0 +1,60 @@
+use minesweeper::grid::{BoardState, CellState, CellView, Grid};
+
+/** Picks the next move from what a player can see: flags a number's
+covered neighbors once they must all be mines, or uncovers them once its
+mines are all flagged */
+fn next_move(grid: &Grid, cells: &[CellView]) -> Option<(bool, i32, i32)> {
+    let view = |x: i32, y: i32| &cells[(y * grid.width + x) as usize];
+    for cell in cells {
+        let mines = match cell.neighboring_mines {
+            Some(mines) if cell.state == CellState::Exposed => mines as usize,
+            _ => continue,
+        };
+        let neighbors = grid.neighbor_coords(cell.x, cell.y);
+        let covered = neighbors
+            .iter()
+            .filter(|&&(x, y)| view(x, y).state == CellState::Covered)
+            .collect::<Vec<_>>();
+        let flagged = neighbors
+            .iter()
+            .filter(|&&(x, y)| view(x, y).state == CellState::Flagged)
+            .count();
+        if let Some(&&(x, y)) = covered.first() {
+            if flagged + covered.len() == mines {
+                return Some((true, x, y));
+            }
+            if flagged == mines {
+                return Some((false, x, y));
+            }
+        }
+    }
+    None
+}
+
+#[test]
+fn a_bot_clears_a_board_through_snapshots() {
+    let mut grid = Grid::with_mines(5, 5, &[(1, 0), (3, 4), (0, 3)]);
+    let mut board_state = grid.uncover(2, 2);
+    let mut moves = 0;
+    while board_state == BoardState::InProgress {
+        let cells = grid.snapshot();
+        // Only exposed cells say what's under them
+        assert!(cells
+            .iter()
+            .all(|cell| cell.has_mine.is_some() == (cell.state == CellState::Exposed)));
+        match next_move(&grid, &cells) {
+            Some((true, x, y)) => {
+                grid.flag(x, y);
+            }
+            Some((false, x, y)) => board_state = grid.uncover(x, y),
+            None => panic!("stuck after {} moves", moves),
+        }
+        moves += 1;
+    }
+    assert_eq!(board_state, BoardState::Cleared);
+    assert!(moves > 0);
+    assert!(grid
+        .snapshot()
+        .iter()
+        .all(|cell| cell.has_mine != Some(true)));
+}